@@ -0,0 +1,105 @@
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+use slava::{bufread::BufRead, socket::{TcpListener, TcpStream}, Slava};
+
+const LINE_LEN: usize = 1024;
+const N_LINES: usize = 20000;
+
+async fn read_line_bytewise(tcp_stream: &TcpStream, buffer: &mut Vec<u8>) -> IOResult<Option<String>> {
+    loop {
+        let mut buf = [0; 1];
+
+        match tcp_stream.read_bytes(&mut buf).await? {
+            0 if buffer.is_empty() => return Ok(None),
+            0 => return Err(IOError::new(ErrorKind::UnexpectedEof, "connection closed in the middle of a line")),
+            _ => {
+                buffer.push(buf[0]);
+                if buf[0] == b'\n' {
+                    let line = String::from_utf8_lossy(buffer).to_string();
+                    buffer.clear();
+                    return Ok(Some(line));
+                }
+            }
+        }
+    }
+}
+
+async fn connected_pair() -> IOResult<(TcpStream, TcpStream)> {
+    let mut listener = TcpListener::bind(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0))?;
+    let addr = listener.local_addr()?;
+    let client = TcpStream::connect(addr).await?;
+    let (server, _) = listener.accept().await?;
+    Ok((client, server))
+}
+
+async fn write_lines(mut tcp_stream: TcpStream) -> IOResult<()> {
+    let mut line = vec![b'x'; LINE_LEN - 1];
+    line.push(b'\n');
+    for _ in 0..N_LINES {
+        let mut written = 0;
+        while written < line.len() {
+            written += tcp_stream.write_bytes(&line[written..]).await?;
+        }
+    }
+    Ok(())
+}
+
+async fn bench_bytewise(slava: &Slava) -> IOResult<(usize, Duration)> {
+    let (client, server) = connected_pair().await?;
+    slava.spawn(async move { write_lines(client).await.unwrap() });
+
+    let start = Instant::now();
+    let mut buffer = Vec::new();
+    let mut n_lines = 0;
+    while read_line_bytewise(&server, &mut buffer).await?.is_some() {
+        n_lines += 1;
+    }
+    Ok((n_lines, start.elapsed()))
+}
+
+async fn bench_buffered(slava: &Slava, capacity: usize) -> IOResult<(usize, Duration)> {
+    let (client, server) = connected_pair().await?;
+    slava.spawn(async move { write_lines(client).await.unwrap() });
+
+    let start = Instant::now();
+    let mut bufread = BufRead::with_capacity(capacity, &server);
+    let mut n_lines = 0;
+    while bufread.read_line().await?.is_some() {
+        n_lines += 1;
+    }
+    Ok((n_lines, start.elapsed()))
+}
+
+fn report(name: &str, result: IOResult<(usize, Duration)>) {
+    match result {
+        Ok((n_lines, elapsed)) => {
+            let mib = (n_lines * LINE_LEN) as f64 / (1024.0 * 1024.0);
+            eprintln!(
+                "{:<24} {:>6} lines in {:>10.3?} ({:.1} MiB/s)",
+                name,
+                n_lines,
+                elapsed,
+                mib / elapsed.as_secs_f64()
+            );
+        },
+        Err(e) => eprintln!("{:<24} failed: {}", name, e)
+    }
+}
+
+fn main() {
+    let slava = Slava::slava();
+    let slava1 = slava.clone();
+
+    slava.spawn(async move {
+        eprintln!("reading {} lines of {} bytes each", N_LINES, LINE_LEN);
+        report("byte per read", bench_bytewise(&slava1).await);
+        for capacity in [512, 4096, 8192, 65536] {
+            report(&format!("BufRead({})", capacity), bench_buffered(&slava1, capacity).await);
+        }
+        std::process::exit(0);
+    });
+
+    slava.run(2);
+}
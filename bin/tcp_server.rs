@@ -1,51 +1,103 @@
-use slava::{bufread::BufRead, socket::TcpListener, Slava};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-const HTTP_HEADER: &'static [u8] = b"HTTP/1.1 200 OK\r
+use slava::{fs::File, bufread::BufRead, server::serve, socket::TcpListener, Slava};
+
+const HTTP_HEADER: &[u8] = b"HTTP/1.1 200 OK\r
 Server: slava/slava-http\r
 Content-Type: video/mp4\r
 Connection: close\r
 \r
 ";
-const CONGRATULATIONS: &'static [u8] = include_bytes!("omedetou.mp4");
+const DEFAULT_CONGRATULATIONS: &str = "bin/omedetou.mp4";
+const MAX_CONNECTIONS: usize = 1024;
 
 fn main() {
+    let congratulations: Arc<Path> = std::env::args_os().nth(1)
+        .map_or_else(|| DEFAULT_CONGRATULATIONS.into(), PathBuf::from)
+        .into();
     let slava = Slava::slava();
     let slava1 = slava.clone();
 
+    let tcp_listener = match TcpListener::new(4396) {
+        Ok(tcp_listener) => tcp_listener,
+        Err(e) => {
+            eprintln!("error listening on port 4396: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     slava.spawn(async move {
-        let mut tcp_listener = TcpListener::new(4396);
         eprintln!("slava server started listening on port 4396");
 
-        loop {
-            match tcp_listener.accept().await {
-                Ok(mut stream) => {
-                    eprintln!("accepting connection");
-                    slava1.spawn(async move {
-                        let mut bufread = BufRead::new(&stream);
-                        let request_line = match bufread.read_line().await {
-                            Ok(line) => line,
-                            Err(e) => {
-                                eprintln!("error reading HTTP request: {}", e);
-                                return;
-                            }
-                        };
-                        eprintln!("read request line: {}", request_line.trim());
-
-                        if let Err(e) = stream.write_bytes(HTTP_HEADER).await {
-                            eprintln!("error writing HTTP header: {}", e);
-                            return;
-                        }
-
-                        if let Err(e) = stream.write_bytes(CONGRATULATIONS).await {
-                            eprintln!("error writing HTTP payload: {}", e);
-                            return;
-                        }
-
-                        eprintln!("done serving contents");
-                    });
+        let server = serve(slava1, tcp_listener, MAX_CONNECTIONS, move |mut stream, peer_addr| {
+            let congratulations = congratulations.clone();
+            async move {
+                eprintln!("accepting connection from {}", peer_addr);
+                let mut bufread = BufRead::new(&stream);
+                let request_line = match bufread.read_line().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) => {
+                        eprintln!("connection closed before sending HTTP request");
+                        return;
+                    }
+                    Err(e) => {
+                        eprintln!("error reading HTTP request: {}", e);
+                        return;
+                    }
+                };
+                eprintln!("read request line: {}", request_line.trim());
+
+                let file = match File::open(&congratulations).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("error opening {}: {}", congratulations.display(), e);
+                        return;
+                    }
+                };
+                let file_len = match file.metadata().await {
+                    Ok(metadata) => metadata.len() as usize,
+                    Err(e) => {
+                        eprintln!("error reading metadata of {}: {}", congratulations.display(), e);
+                        return;
+                    }
+                };
+
+                if let Err(e) = stream.set_cork(true) {
+                    eprintln!("error corking TCP stream: {}", e);
+                    return;
+                }
+
+                if let Err(e) = stream.write_bytes(HTTP_HEADER).await {
+                    eprintln!("error writing HTTP header: {}", e);
+                    return;
                 }
-                Err(e) => eprintln!("error accepting TCP connection: {}", e)
+
+                let file = match file.into_std().await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("error preparing {} for sendfile: {}", congratulations.display(), e);
+                        return;
+                    }
+                };
+
+                if let Err(e) = stream.sendfile(&file, 0, file_len).await {
+                    eprintln!("error writing HTTP payload: {}", e);
+                    return;
+                }
+
+                if let Err(e) = stream.set_cork(false) {
+                    eprintln!("error uncorking TCP stream: {}", e);
+                    return;
+                }
+
+                eprintln!("done serving contents");
             }
+        });
+
+        if let Err(e) = server.await {
+            eprintln!("server stopped: {}", e);
+            std::process::exit(1);
         }
     });
 
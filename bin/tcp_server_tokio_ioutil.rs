@@ -1,50 +1,98 @@
-use slava::{socket::TcpListener, Slava};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use slava::{fs::File, server::serve, socket::TcpListener, Slava};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
-const HTTP_HEADER: &'static [u8] = b"HTTP/1.1 200 OK\r
+const HTTP_HEADER: &[u8] = b"HTTP/1.1 200 OK\r
 Server: slava/slava-http-tokio-ioutil-mixed\r
 Content-Type: audio/mpeg\r
 Connection: close\r
 \r
 ";
-const DOKI_DOKI: &'static [u8] = include_bytes!("doki_doki_forever.mp3");
+const DEFAULT_DOKI_DOKI: &str = "bin/doki_doki_forever.mp3";
+const MAX_CONNECTIONS: usize = 1024;
 
 fn main() {
+    let doki_doki: Arc<Path> = std::env::args_os().nth(1)
+        .map_or_else(|| DEFAULT_DOKI_DOKI.into(), PathBuf::from)
+        .into();
     let slava = Slava::slava();
     let slava1 = slava.clone();
 
+    let tcp_listener = match TcpListener::new(4398) {
+        Ok(tcp_listener) => tcp_listener,
+        Err(e) => {
+            eprintln!("error listening on port 4398: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     slava.spawn(async move {
-        let mut tcp_listener = TcpListener::new(4398);
         eprintln!("slava server started listening on port 4398");
 
-        loop {
-            match tcp_listener.accept().await {
-                Ok(mut stream) => {
-                    eprintln!("accepting connection");
-                    slava1.spawn(async move {
-                        let mut buf_reader = BufReader::new(&mut stream);
-                        let mut request_line = String::new();
-                        if let Err(e) =  buf_reader.read_line(&mut request_line).await {
-                            eprintln!("error reading HTTP request: {}", e);
-                            return;
-                        };
-                        eprintln!("read request line: {}", request_line.trim());
-
-                        if let Err(e) = stream.write_all(HTTP_HEADER).await {
-                            eprintln!("error writing HTTP header: {}", e);
-                            return;
-                        }
-
-                        if let Err(e) = stream.write_all(DOKI_DOKI).await {
-                            eprintln!("error writing HTTP payload: {}", e);
-                            return;
-                        }
-
-                        eprintln!("done serving contents");
-                    });
+        let server = serve(slava1, tcp_listener, MAX_CONNECTIONS, move |mut stream, peer_addr| {
+            let doki_doki = doki_doki.clone();
+            async move {
+                eprintln!("accepting connection from {}", peer_addr);
+                let mut buf_reader = BufReader::new(&mut stream);
+                let mut request_line = String::new();
+                if let Err(e) =  buf_reader.read_line(&mut request_line).await {
+                    eprintln!("error reading HTTP request: {}", e);
+                    return;
+                };
+                eprintln!("read request line: {}", request_line.trim());
+
+                let file = match File::open(&doki_doki).await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("error opening {}: {}", doki_doki.display(), e);
+                        return;
+                    }
+                };
+                let file_len = match file.metadata().await {
+                    Ok(metadata) => metadata.len() as usize,
+                    Err(e) => {
+                        eprintln!("error reading metadata of {}: {}", doki_doki.display(), e);
+                        return;
+                    }
+                };
+
+                if let Err(e) = stream.set_cork(true) {
+                    eprintln!("error corking TCP stream: {}", e);
+                    return;
+                }
+
+                if let Err(e) = stream.write_all(HTTP_HEADER).await {
+                    eprintln!("error writing HTTP header: {}", e);
+                    return;
                 }
-                Err(e) => eprintln!("error accepting TCP connection: {}", e)
+
+                let file = match file.into_std().await {
+                    Ok(file) => file,
+                    Err(e) => {
+                        eprintln!("error preparing {} for sendfile: {}", doki_doki.display(), e);
+                        return;
+                    }
+                };
+
+                if let Err(e) = stream.sendfile(&file, 0, file_len).await {
+                    eprintln!("error writing HTTP payload: {}", e);
+                    return;
+                }
+
+                if let Err(e) = stream.set_cork(false) {
+                    eprintln!("error uncorking TCP stream: {}", e);
+                    return;
+                }
+
+                eprintln!("done serving contents");
             }
+        });
+
+        if let Err(e) = server.await {
+            eprintln!("server stopped: {}", e);
+            std::process::exit(1);
         }
     });
 
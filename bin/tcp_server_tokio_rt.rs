@@ -1,24 +1,40 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
 use slava::socket::TcpListener;
+use slava::stream::StreamExt;
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::task::spawn as tokio_spawn;
 
-const HTTP_HEADER: &'static [u8] = b"HTTP/1.1 200 OK\r
+const HTTP_HEADER: &[u8] = b"HTTP/1.1 200 OK\r
 Server: slava-tokio/slava-http-tokio-runtime-mixed\r
 Content-Type: application/pdf\r
 Connection: close\r
 \r
 ";
-const UNDERSTANDING_W: &'static [u8] = include_bytes!("understanding_algorithm_w.pdf");
+const DEFAULT_UNDERSTANDING_W: &str = "bin/understanding_algorithm_w.pdf";
 
 #[tokio::main]
 async fn main() {
-    let mut tcp_listener = TcpListener::new(4397);
+    let understanding_w: Arc<Path> = std::env::args_os().nth(1)
+        .map_or_else(|| DEFAULT_UNDERSTANDING_W.into(), PathBuf::from)
+        .into();
+    let mut tcp_listener = match TcpListener::new(4397) {
+        Ok(tcp_listener) => tcp_listener,
+        Err(e) => {
+            eprintln!("error listening on port 4397: {}", e);
+            std::process::exit(1);
+        }
+    };
     eprintln!("slava/tokio mixed server started listening on port 4397");
 
-    loop {
-        match tcp_listener.accept().await {
-            Ok(mut stream) => {
-                eprintln!("accepting connection");
+    let mut incoming = tcp_listener.incoming();
+    while let Some(accepted) = incoming.next().await {
+        match accepted {
+            Ok((mut stream, peer_addr)) => {
+                eprintln!("accepting connection from {}", peer_addr);
+                let understanding_w = understanding_w.clone();
                 tokio_spawn(async move {
                     let mut buf_reader = BufReader::new(&mut stream);
                     let mut request_line = String::new();
@@ -28,16 +44,41 @@ async fn main() {
                     };
                     eprintln!("read request line: {}", request_line.trim());
 
+                    let file = match File::open(&understanding_w) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            eprintln!("error opening {}: {}", understanding_w.display(), e);
+                            return;
+                        }
+                    };
+                    let file_len = match file.metadata() {
+                        Ok(metadata) => metadata.len() as usize,
+                        Err(e) => {
+                            eprintln!("error reading metadata of {}: {}", understanding_w.display(), e);
+                            return;
+                        }
+                    };
+
+                    if let Err(e) = stream.set_cork(true) {
+                        eprintln!("error corking TCP stream: {}", e);
+                        return;
+                    }
+
                     if let Err(e) = stream.write_all(HTTP_HEADER).await {
                         eprintln!("error writing HTTP header: {}", e);
                         return;
                     }
 
-                    if let Err(e) = stream.write_all(UNDERSTANDING_W).await {
+                    if let Err(e) = stream.sendfile(&file, 0, file_len).await {
                         eprintln!("error writing HTTP payload: {}", e);
                         return;
                     }
 
+                    if let Err(e) = stream.set_cork(false) {
+                        eprintln!("error uncorking TCP stream: {}", e);
+                        return;
+                    }
+
                     eprintln!("done serving contents");
                 });
             }
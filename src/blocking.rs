@@ -0,0 +1,219 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::Result as IOResult;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::spawn as thread_spawn;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded as channel_unbounded, Receiver, RecvTimeoutError, Sender};
+
+use crate::task::{join_channel, JoinHandle};
+
+pub const DEFAULT_MAX_BLOCKING_THREADS: usize = 512;
+pub const DEFAULT_BLOCKING_KEEP_ALIVE: Duration = Duration::from_secs(10);
+
+type BlockingJob = Box<dyn FnOnce() + Send + 'static>;
+
+struct PoolState {
+    sender: Option<Sender<BlockingJob>>,
+    max_threads: usize,
+    keep_alive: Duration,
+    n_threads: usize,
+    n_idle: usize,
+    n_queued: usize
+}
+
+pub(crate) struct BlockingPool {
+    receiver: Receiver<BlockingJob>,
+    state: Mutex<PoolState>
+}
+
+static DEFAULT_BLOCKING_POOL: OnceLock<Arc<BlockingPool>> = OnceLock::new();
+
+thread_local! {
+    static CURRENT_BLOCKING_POOL: RefCell<Option<Arc<BlockingPool>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn set_current_blocking_pool(pool: Arc<BlockingPool>) {
+    CURRENT_BLOCKING_POOL.with(|current| *current.borrow_mut() = Some(pool));
+}
+
+pub(crate) fn current_blocking_pool() -> Arc<BlockingPool> {
+    CURRENT_BLOCKING_POOL
+        .with(|current| current.borrow().clone())
+        .unwrap_or_else(|| DEFAULT_BLOCKING_POOL.get_or_init(BlockingPool::new).clone())
+}
+
+impl BlockingPool {
+    pub(crate) fn new() -> Arc<Self> {
+        let (sender, receiver) = channel_unbounded();
+        Arc::new(Self {
+            receiver,
+            state: Mutex::new(PoolState {
+                sender: Some(sender),
+                max_threads: DEFAULT_MAX_BLOCKING_THREADS,
+                keep_alive: DEFAULT_BLOCKING_KEEP_ALIVE,
+                n_threads: 0,
+                n_idle: 0,
+                n_queued: 0
+            })
+        })
+    }
+
+    pub(crate) fn set_max_threads(&self, max_threads: usize) {
+        self.state.lock().unwrap().max_threads = max_threads.max(1);
+    }
+
+    pub(crate) fn set_keep_alive(&self, keep_alive: Duration) {
+        self.state.lock().unwrap().keep_alive = keep_alive;
+    }
+
+    pub(crate) fn shutdown(&self) {
+        self.state.lock().unwrap().sender = None;
+    }
+
+    pub(crate) fn spawn<T, F>(self: &Arc<Self>, f: F) -> JoinHandle<T>
+        where T: Send + 'static,
+              F: FnOnce() -> T + Send + 'static
+    {
+        let (sender, handle) = join_channel();
+        let _ = self.execute(Box::new(move || match catch_unwind(AssertUnwindSafe(f)) {
+            Ok(result) => sender.send(result),
+            Err(panic) => sender.send_panic(panic)
+        }));
+        handle
+    }
+
+    pub(crate) fn execute(self: &Arc<Self>, job: BlockingJob) -> Result<(), BlockingJob> {
+        let mut state = self.state.lock().unwrap();
+        let Some(sender) = &state.sender else {
+            return Err(job);
+        };
+        sender.send(job).unwrap();
+
+        if state.n_idle > 0 {
+            state.n_idle -= 1;
+        } else if state.n_threads < state.max_threads {
+            state.n_threads += 1;
+            let pool = self.clone();
+            thread_spawn(move || pool.run_worker());
+        } else {
+            state.n_queued += 1;
+        }
+        Ok(())
+    }
+
+    fn run_worker(self: Arc<Self>) {
+        set_current_blocking_pool(self.clone());
+
+        loop {
+            let keep_alive = self.state.lock().unwrap().keep_alive;
+            match self.receiver.recv_timeout(keep_alive) {
+                Ok(job) => {
+                    job();
+
+                    let mut state = self.state.lock().unwrap();
+                    if state.n_queued > 0 {
+                        state.n_queued -= 1;
+                    } else {
+                        state.n_idle += 1;
+                    }
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    let mut state = self.state.lock().unwrap();
+                    if state.n_idle > 0 {
+                        state.n_idle -= 1;
+                        state.n_threads -= 1;
+                        return;
+                    }
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    let mut state = self.state.lock().unwrap();
+                    state.n_idle = state.n_idle.saturating_sub(1);
+                    state.n_threads -= 1;
+                    return;
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn run_blocking<T, F>(f: F) -> JoinHandle<T>
+    where T: Send + 'static,
+          F: FnOnce() -> T + Send + 'static
+{
+    current_blocking_pool().spawn(f)
+}
+
+pub(crate) fn run_blocking_io<T, F>(f: F) -> impl Future<Output=IOResult<T>> + Send + Sync
+    where T: Send + 'static,
+          F: FnOnce() -> IOResult<T> + Send + 'static
+{
+    let handle = run_blocking(f);
+    async move { handle.await? }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread::sleep;
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn pool_caps_threads_and_queues_excess_jobs() {
+        let pool = BlockingPool::new();
+        pool.set_max_threads(2);
+        let active = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+
+        let handles = (0..6).map(|i| {
+            let (active, peak) = (active.clone(), peak.clone());
+            pool.spawn(move || {
+                peak.fetch_max(active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                sleep(Duration::from_millis(20));
+                active.fetch_sub(1, Ordering::SeqCst);
+                i
+            })
+        }).collect::<Vec<_>>();
+
+        let results = block_on(async move {
+            let mut results = Vec::new();
+            for handle in handles {
+                results.push(handle.await.unwrap());
+            }
+            results
+        });
+        assert_eq!(results, (0..6).collect::<Vec<_>>());
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.state.lock().unwrap().n_threads, 2);
+    }
+
+    #[test]
+    fn idle_threads_exit_after_keep_alive() {
+        let pool = BlockingPool::new();
+        pool.set_keep_alive(Duration::from_millis(50));
+        let handles = (0..3).map(|_| pool.spawn(|| sleep(Duration::from_millis(10)))).collect::<Vec<_>>();
+        block_on(async move {
+            for handle in handles {
+                handle.await.unwrap();
+            }
+        });
+
+        sleep(Duration::from_millis(300));
+        let state = pool.state.lock().unwrap();
+        assert_eq!((state.n_threads, state.n_idle), (0, 0));
+    }
+
+    #[test]
+    fn shutdown_rejects_new_jobs() {
+        let pool = BlockingPool::new();
+        assert_eq!(block_on(pool.spawn(|| 1)).unwrap(), 1);
+
+        pool.shutdown();
+        let e = block_on(pool.spawn(|| 2)).unwrap_err();
+        assert!(e.is_cancelled());
+    }
+}
@@ -1,34 +1,277 @@
-use crate::socket::TcpStream;
+use std::future::poll_fn;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::mem::take;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
 
-pub struct BufRead<'a> {
-    buffer: Vec<u8>,
-    tcp_stream: &'a TcpStream
+use crate::io::AsyncRead;
+use crate::stream::Stream;
+
+pub const DEFAULT_BUF_SIZE: usize = 8192;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 64 * 1024;
+
+pub struct BufRead<S> {
+    buf: Box<[u8]>,
+    pos: usize,
+    filled: usize,
+    line: Vec<u8>,
+    max_line_length: Option<usize>,
+    is_discarding: bool,
+    stream: S
 }
 
-impl<'a> BufRead<'a> {
-    pub fn new(tcp_stream: &'a TcpStream) -> Self {
+impl<S: AsyncRead + Unpin> BufRead<S> {
+    pub fn new(stream: S) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, stream)
+    }
+
+    pub fn with_capacity(capacity: usize, stream: S) -> Self {
         Self {
-            buffer: Vec::new(),
-            tcp_stream
+            buf: vec![0; capacity.max(1)].into_boxed_slice(),
+            pos: 0,
+            filled: 0,
+            line: Vec::new(),
+            max_line_length: Some(DEFAULT_MAX_LINE_LENGTH),
+            is_discarding: false,
+            stream
         }
     }
 
-    pub async fn read_line(&mut self) -> Result<String, String> {
+    pub fn capacity(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..self.filled]
+    }
+
+    pub fn set_max_line_length(&mut self, max_line_length: Option<usize>) {
+        self.max_line_length = max_line_length;
+    }
+
+    pub fn max_line_length(&self) -> Option<usize> {
+        self.max_line_length
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    pub fn into_inner(self) -> (S, Vec<u8>) {
+        let mut buffered = self.line;
+        buffered.extend_from_slice(&self.buf[self.pos..self.filled]);
+        (self.stream, buffered)
+    }
+
+    pub fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<&[u8]>> {
+        if self.pos >= self.filled {
+            self.filled = ready!(Pin::new(&mut self.stream).poll_read(cx, &mut self.buf))?;
+            self.pos = 0;
+        }
+        Poll::Ready(Ok(&self.buf[self.pos..self.filled]))
+    }
+
+    pub async fn fill_buf(&mut self) -> IOResult<&[u8]> {
+        poll_fn(|cx| self.poll_fill_buf(cx).map_ok(|_| ())).await?;
+        Ok(self.buffer())
+    }
+
+    pub fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.filled);
+    }
+
+    pub async fn read_line(&mut self) -> IOResult<Option<String>> {
+        poll_fn(|cx| self.poll_read_line(cx)).await
+    }
+
+    pub async fn read_until(&mut self, delim: u8, out: &mut Vec<u8>) -> IOResult<usize> {
+        let start = out.len();
+        poll_fn(|cx| self.poll_read_until(cx, delim, out, start)).await?;
+        Ok(out.len() - start)
+    }
+
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> IOResult<()> {
+        let mut read = 0;
+        while read < buf.len() {
+            let available = self.fill_buf().await?;
+            if available.is_empty() {
+                return Err(IOError::new(ErrorKind::UnexpectedEof, "connection closed before enough bytes were read"));
+            }
+
+            let n = available.len().min(buf.len() - read);
+            buf[read..read + n].copy_from_slice(&available[..n]);
+            self.consume(n);
+            read += n;
+        }
+        Ok(())
+    }
+
+    pub async fn read_to_end(&mut self, out: &mut Vec<u8>) -> IOResult<usize> {
+        let start = out.len();
         loop {
-            let mut buf = [0; 1];
-
-            match self.tcp_stream.read_bytes(&mut buf).await {
-                Ok(0) => return Err("EOF".to_string()),
-                Ok(_) => {
-                    self.buffer.push(buf[0]);
-                    if buf[0] == b'\n' {
-                        let line = String::from_utf8_lossy(&self.buffer).to_string();
-                        self.buffer.clear();
-                        return Ok(line);
-                    }
+            let available = self.fill_buf().await?;
+            if available.is_empty() {
+                return Ok(out.len() - start);
+            }
+
+            let n = available.len();
+            out.extend_from_slice(available);
+            self.consume(n);
+        }
+    }
+
+    pub fn lines(self) -> Lines<S> {
+        Lines { bufread: self }
+    }
+
+    fn poll_read_line(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<Option<String>>> {
+        let mut line = take(&mut self.line);
+        let result = match self.poll_read_until(cx, b'\n', &mut line, 0) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Ok(true)) => {
+                let result = String::from_utf8_lossy(&line).to_string();
+                line.clear();
+                Poll::Ready(Ok(Some(result)))
+            },
+            Poll::Ready(Ok(false)) if line.is_empty() => Poll::Ready(Ok(None)),
+            Poll::Ready(Ok(false)) => {
+                line.clear();
+                Poll::Ready(Err(IOError::new(ErrorKind::UnexpectedEof, "connection closed in the middle of a line")))
+            },
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e))
+        };
+        self.line = line;
+        result
+    }
+
+    fn poll_read_until(
+        &mut self,
+        cx: &mut Context<'_>,
+        delim: u8,
+        out: &mut Vec<u8>,
+        start: usize
+    ) -> Poll<IOResult<bool>> {
+        while self.is_discarding {
+            ready!(self.poll_fill_buf(cx))?;
+            let available = &self.buf[self.pos..self.filled];
+            if available.is_empty() {
+                self.is_discarding = false;
+                return Poll::Ready(Ok(false));
+            }
+
+            match available.iter().position(|b| *b == delim) {
+                Some(idx) => {
+                    self.pos += idx + 1;
+                    self.is_discarding = false;
                 },
-                Err(e) => return Err(e)
+                None => self.pos = self.filled
+            }
+        }
+
+        loop {
+            ready!(self.poll_fill_buf(cx))?;
+            let available = &self.buf[self.pos..self.filled];
+            if available.is_empty() {
+                return Poll::Ready(Ok(false));
+            }
+
+            let (used, done) = match available.iter().position(|b| *b == delim) {
+                Some(idx) => (idx + 1, true),
+                None => (available.len(), false)
+            };
+
+            if let Some(max_line_length) = self.max_line_length
+                && out.len() - start + used - done as usize > max_line_length {
+                self.pos += used;
+                self.is_discarding = !done;
+                out.truncate(start);
+                return Poll::Ready(Err(IOError::new(ErrorKind::InvalidData, "line exceeds maximum length")));
+            }
+
+            out.extend_from_slice(&available[..used]);
+            self.pos += used;
+            if done {
+                return Poll::Ready(Ok(true));
             }
         }
     }
 }
+
+impl<S: AsyncRead + Unpin> AsyncRead for BufRead<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        let this = self.get_mut();
+        if this.pos >= this.filled && buf.len() >= this.buf.len() {
+            return Pin::new(&mut this.stream).poll_read(cx, buf);
+        }
+
+        let available = ready!(this.poll_fill_buf(cx))?;
+        let n = available.len().min(buf.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        this.consume(n);
+        Poll::Ready(Ok(n))
+    }
+}
+
+pub struct Lines<S> {
+    bufread: BufRead<S>
+}
+
+impl<S> Lines<S> {
+    pub fn into_inner(self) -> BufRead<S> {
+        self.bufread
+    }
+}
+
+impl<S: AsyncRead + Unpin> Stream for Lines<S> {
+    type Item = IOResult<String>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().bufread.poll_read_line(cx).map(|result| result.transpose())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block_on;
+    use crate::io::{duplex, AsyncWriteExt};
+
+    #[test]
+    fn read_line_discards_rest_of_overlong_line() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut bufread = BufRead::with_capacity(4, server);
+            bufread.set_max_line_length(Some(8));
+
+            client.write_all(b"short\n0123456789").await.unwrap();
+            assert_eq!(bufread.read_line().await.unwrap().as_deref(), Some("short\n"));
+            assert_eq!(bufread.read_line().await.unwrap_err().kind(), ErrorKind::InvalidData);
+
+            client.write_all(b"abcdef\nnext\n").await.unwrap();
+            client.shutdown().await.unwrap();
+            assert_eq!(bufread.read_line().await.unwrap().as_deref(), Some("next\n"));
+            assert_eq!(bufread.read_line().await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn read_until_resumes_after_delimiter_in_overlong_chunk() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut bufread = BufRead::with_capacity(32, server);
+            bufread.set_max_line_length(Some(4));
+
+            client.write_all(b"toolong;ok;").await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let mut out = Vec::new();
+            assert!(bufread.read_until(b';', &mut out).await.is_err());
+            assert_eq!(bufread.read_until(b';', &mut out).await.unwrap(), 3);
+            assert_eq!(out, b"ok;");
+        });
+    }
+}
@@ -0,0 +1,233 @@
+use std::io::{Error as IOError, ErrorKind, IoSlice, Result as IOResult};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::bufread::DEFAULT_BUF_SIZE;
+use crate::io::{AsyncRead, AsyncWrite};
+
+pub struct BufWriter<W> {
+    buf: Vec<u8>,
+    written: usize,
+    writer: W
+}
+
+impl<W: AsyncWrite + Unpin> BufWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_capacity(DEFAULT_BUF_SIZE, writer)
+    }
+
+    pub fn with_capacity(capacity: usize, writer: W) -> Self {
+        Self {
+            buf: Vec::with_capacity(capacity.max(1)),
+            written: 0,
+            writer
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.buf.capacity()
+    }
+
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.written..]
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(mut self) -> (W, Vec<u8>) {
+        self.buf.drain(..self.written);
+        (self.writer, self.buf)
+    }
+
+    fn poll_flush_buf(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        while self.written < self.buf.len() {
+            match ready!(Pin::new(&mut self.writer).poll_write(cx, &self.buf[self.written..])) {
+                Ok(0) => return Poll::Ready(Err(IOError::new(ErrorKind::WriteZero, "failed to write buffered data"))),
+                Ok(n) => self.written += n,
+                Err(e) => return Poll::Ready(Err(e))
+            }
+        }
+
+        self.buf.clear();
+        self.written = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn spare_capacity(&self) -> usize {
+        self.buf.capacity() - self.buf.len()
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncWrite for BufWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        let this = self.get_mut();
+        if buf.len() > this.spare_capacity() {
+            ready!(this.poll_flush_buf(cx))?;
+        }
+
+        if buf.len() >= this.buf.capacity() {
+            return Pin::new(&mut this.writer).poll_write(cx, buf);
+        }
+
+        this.buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        let this = self.get_mut();
+        let total_len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+        if total_len > this.spare_capacity() {
+            ready!(this.poll_flush_buf(cx))?;
+        }
+
+        if total_len >= this.buf.capacity() {
+            return Pin::new(&mut this.writer).poll_write_vectored(cx, bufs);
+        }
+
+        for buf in bufs {
+            this.buf.extend_from_slice(buf);
+        }
+        Poll::Ready(Ok(total_len))
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buf(cx))?;
+        Pin::new(&mut this.writer).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buf(cx))?;
+        Pin::new(&mut this.writer).poll_shutdown(cx)
+    }
+}
+
+impl<W: AsyncRead + Unpin> AsyncRead for BufWriter<W> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        Pin::new(&mut self.get_mut().writer).poll_read(cx, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+    use crate::block_on;
+    use crate::io::{duplex, AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+    async fn is_pending(reader: &mut DuplexStream) -> bool {
+        let mut buf = [0u8; 1];
+        poll_fn(|cx| Poll::Ready(Pin::new(&mut *reader).poll_read(cx, &mut buf).is_pending())).await
+    }
+
+    struct ZeroWriter;
+
+    impl AsyncWrite for ZeroWriter {
+        fn poll_write(self: Pin<&mut Self>, _cx: &mut Context<'_>, _buf: &[u8]) -> Poll<IOResult<usize>> {
+            Poll::Ready(Ok(0))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn small_writes_stay_buffered_until_flush() {
+        block_on(async {
+            let (client, mut server) = duplex(64);
+            let mut writer = BufWriter::with_capacity(16, client);
+
+            writer.write_all(b"ab").await.unwrap();
+            writer.write_all(b"cd").await.unwrap();
+            assert_eq!(writer.buffer(), b"abcd");
+            assert!(is_pending(&mut server).await);
+
+            writer.flush().await.unwrap();
+            assert!(writer.buffer().is_empty());
+            let mut received = [0u8; 4];
+            server.read_exact(&mut received).await.unwrap();
+            assert_eq!(&received, b"abcd");
+        });
+    }
+
+    #[test]
+    fn large_write_bypasses_buffer() {
+        block_on(async {
+            let (client, mut server) = duplex(64);
+            let mut writer = BufWriter::with_capacity(8, client);
+
+            assert_eq!(writer.write(&[7u8; 32]).await.unwrap(), 32);
+            assert!(writer.buffer().is_empty());
+            let mut received = [0u8; 32];
+            server.read_exact(&mut received).await.unwrap();
+            assert_eq!(received, [7u8; 32]);
+        });
+    }
+
+    #[test]
+    fn full_buffer_flushes_before_vectored_write() {
+        block_on(async {
+            let (client, mut server) = duplex(64);
+            let mut writer = BufWriter::with_capacity(8, client);
+
+            writer.write_all(b"abcdef").await.unwrap();
+            let bufs = [IoSlice::new(b"gh"), IoSlice::new(b"ij")];
+            let n = poll_fn(|cx| Pin::new(&mut writer).poll_write_vectored(cx, &bufs)).await.unwrap();
+            assert_eq!(n, 4);
+            assert_eq!(writer.buffer(), b"ghij");
+
+            let mut received = [0u8; 6];
+            server.read_exact(&mut received).await.unwrap();
+            assert_eq!(&received, b"abcdef");
+            assert!(is_pending(&mut server).await);
+        });
+    }
+
+    #[test]
+    fn write_zero_is_propagated() {
+        block_on(async {
+            let mut writer = BufWriter::with_capacity(8, ZeroWriter);
+            writer.write_all(b"abc").await.unwrap();
+            assert_eq!(writer.flush().await.unwrap_err().kind(), ErrorKind::WriteZero);
+            assert_eq!(writer.buffer(), b"abc");
+        });
+    }
+
+    #[test]
+    fn tokio_write_flushes_buffer() {
+        block_on(async {
+            let (client, mut server) = duplex(64);
+            let mut writer = BufWriter::with_capacity(16, client);
+
+            tokio::io::AsyncWriteExt::write_all(&mut writer, b"tokio").await.unwrap();
+            assert_eq!(writer.buffer(), b"tokio");
+            tokio::io::AsyncWriteExt::flush(&mut writer).await.unwrap();
+            assert!(writer.buffer().is_empty());
+
+            let mut received = [0u8; 5];
+            server.read_exact(&mut received).await.unwrap();
+            assert_eq!(&received, b"tokio");
+        });
+    }
+}
@@ -0,0 +1,620 @@
+use std::future::poll_fn;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use crate::bufread::{DEFAULT_BUF_SIZE, DEFAULT_MAX_LINE_LENGTH};
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::stream::{Sink, Stream};
+
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+pub trait Decoder {
+    type Item;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> IOResult<Option<Self::Item>>;
+
+    fn decode_eof(&mut self, src: &mut Vec<u8>) -> IOResult<Option<Self::Item>> {
+        match self.decode(src)? {
+            Some(frame) => Ok(Some(frame)),
+            None if src.is_empty() => Ok(None),
+            None => Err(IOError::new(ErrorKind::UnexpectedEof, "bytes remaining on stream"))
+        }
+    }
+}
+
+pub trait Encoder<Item> {
+    fn encode(&mut self, item: Item, dst: &mut Vec<u8>) -> IOResult<()>;
+}
+
+pub struct Framed<T, C> {
+    io: T,
+    codec: C,
+    read_buf: Vec<u8>,
+    write_buf: Vec<u8>,
+    is_readable: bool,
+    eof: bool
+}
+
+impl<T, C> Framed<T, C> {
+    pub fn new(io: T, codec: C) -> Self {
+        Self {
+            io,
+            codec,
+            read_buf: Vec::new(),
+            write_buf: Vec::new(),
+            is_readable: false,
+            eof: false
+        }
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.io
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.io
+    }
+
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut C {
+        &mut self.codec
+    }
+
+    pub fn read_buffer(&self) -> &[u8] {
+        &self.read_buf
+    }
+
+    pub fn write_buffer(&self) -> &[u8] {
+        &self.write_buf
+    }
+
+    pub fn into_inner(self) -> T {
+        self.io
+    }
+}
+
+impl<T: AsyncRead + Unpin, C: Decoder + Unpin> Stream for Framed<T, C> {
+    type Item = IOResult<C::Item>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if this.is_readable {
+                if this.eof {
+                    let frame = this.codec.decode_eof(&mut this.read_buf);
+                    if frame.is_err() {
+                        this.read_buf.clear();
+                    }
+                    if let Ok(None) = frame {
+                        this.is_readable = false;
+                    }
+                    return Poll::Ready(frame.transpose());
+                }
+
+                if let Some(frame) = this.codec.decode(&mut this.read_buf)? {
+                    return Poll::Ready(Some(Ok(frame)));
+                }
+                this.is_readable = false;
+            }
+
+            if this.eof {
+                return Poll::Ready(None);
+            }
+
+            let len = this.read_buf.len();
+            this.read_buf.resize(len + DEFAULT_BUF_SIZE, 0);
+            let result = Pin::new(&mut this.io).poll_read(cx, &mut this.read_buf[len..]);
+            match result {
+                Poll::Ready(Ok(n)) => {
+                    this.read_buf.truncate(len + n);
+                    this.eof = n == 0;
+                    this.is_readable = true;
+                },
+                Poll::Ready(Err(e)) => {
+                    this.read_buf.truncate(len);
+                    return Poll::Ready(Some(Err(e)));
+                },
+                Poll::Pending => {
+                    this.read_buf.truncate(len);
+                    return Poll::Pending;
+                }
+            }
+        }
+    }
+}
+
+impl<T, C> Framed<T, C> where T: AsyncWrite + Unpin {
+    pub async fn flush(&mut self) -> IOResult<()> {
+        poll_fn(|cx| self.poll_flush_frames(cx)).await
+    }
+
+    pub async fn close(&mut self) -> IOResult<()> {
+        poll_fn(|cx| self.poll_close_frames(cx)).await
+    }
+
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        while !self.write_buf.is_empty() {
+            let n = ready!(Pin::new(&mut self.io).poll_write(cx, &self.write_buf))?;
+            if n == 0 {
+                return Poll::Ready(Err(IOError::new(ErrorKind::WriteZero, "failed to write frame to transport")));
+            }
+            self.write_buf.drain(..n);
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_frames(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        ready!(self.poll_write_buf(cx))?;
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_close_frames(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        ready!(self.poll_flush_frames(cx))?;
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+}
+
+impl<T, C, I> Sink<I> for Framed<T, C>
+    where T: AsyncWrite + Unpin,
+          C: Encoder<I> + Unpin
+{
+    type Error = IOError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        let this = self.get_mut();
+        if this.write_buf.len() >= DEFAULT_BUF_SIZE {
+            ready!(this.poll_write_buf(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: I) -> IOResult<()> {
+        let this = self.get_mut();
+        this.codec.encode(item, &mut this.write_buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().poll_flush_frames(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().poll_close_frames(cx)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BytesCodec;
+
+impl BytesCodec {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> IOResult<Option<Vec<u8>>> {
+        if src.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(std::mem::take(src)))
+    }
+}
+
+impl Encoder<Vec<u8>> for BytesCodec {
+    fn encode(&mut self, item: Vec<u8>, dst: &mut Vec<u8>) -> IOResult<()> {
+        dst.extend_from_slice(&item);
+        Ok(())
+    }
+}
+
+impl Encoder<&[u8]> for BytesCodec {
+    fn encode(&mut self, item: &[u8], dst: &mut Vec<u8>) -> IOResult<()> {
+        dst.extend_from_slice(item);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LinesCodec {
+    max_length: usize,
+    next_index: usize,
+    is_discarding: bool
+}
+
+impl Default for LinesCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LinesCodec {
+    pub fn new() -> Self {
+        Self::new_with_max_length(DEFAULT_MAX_LINE_LENGTH)
+    }
+
+    pub fn new_with_max_length(max_length: usize) -> Self {
+        Self { max_length, next_index: 0, is_discarding: false }
+    }
+
+    pub fn max_length(&self) -> usize {
+        self.max_length
+    }
+}
+
+impl Decoder for LinesCodec {
+    type Item = String;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> IOResult<Option<String>> {
+        loop {
+            let newline = src[self.next_index..].iter().position(|b| *b == b'\n').map(|idx| self.next_index + idx);
+
+            if self.is_discarding {
+                match newline {
+                    Some(idx) => {
+                        src.drain(..=idx);
+                        self.next_index = 0;
+                        self.is_discarding = false;
+                        continue;
+                    },
+                    None => {
+                        src.clear();
+                        self.next_index = 0;
+                        return Ok(None);
+                    }
+                }
+            }
+
+            return match newline {
+                Some(idx) if idx <= self.max_length => {
+                    let mut line: Vec<u8> = src.drain(..=idx).collect();
+                    self.next_index = 0;
+                    line.pop();
+                    if line.last() == Some(&b'\r') {
+                        line.pop();
+                    }
+                    String::from_utf8(line)
+                        .map(Some)
+                        .map_err(|_| IOError::new(ErrorKind::InvalidData, "line is not valid UTF-8"))
+                },
+                Some(_) => {
+                    self.is_discarding = true;
+                    Err(IOError::new(ErrorKind::InvalidData, "line exceeds maximum length"))
+                },
+                None if src.len() > self.max_length => {
+                    self.is_discarding = true;
+                    Err(IOError::new(ErrorKind::InvalidData, "line exceeds maximum length"))
+                },
+                None => {
+                    self.next_index = src.len();
+                    Ok(None)
+                }
+            };
+        }
+    }
+
+    fn decode_eof(&mut self, src: &mut Vec<u8>) -> IOResult<Option<String>> {
+        if let Some(line) = self.decode(src)? {
+            return Ok(Some(line));
+        }
+
+        self.next_index = 0;
+        if src.is_empty() || self.is_discarding {
+            src.clear();
+            return Ok(None);
+        }
+
+        let mut line = std::mem::take(src);
+        if line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        String::from_utf8(line)
+            .map(Some)
+            .map_err(|_| IOError::new(ErrorKind::InvalidData, "line is not valid UTF-8"))
+    }
+}
+
+impl<S: AsRef<str>> Encoder<S> for LinesCodec {
+    fn encode(&mut self, item: S, dst: &mut Vec<u8>) -> IOResult<()> {
+        dst.extend_from_slice(item.as_ref().as_bytes());
+        dst.push(b'\n');
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LengthDelimitedCodec {
+    length_field_length: usize,
+    little_endian: bool,
+    max_frame_length: usize,
+    discard_remaining: u64
+}
+
+impl Default for LengthDelimitedCodec {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LengthDelimitedCodec {
+    pub fn new() -> Self {
+        Self {
+            length_field_length: 4,
+            little_endian: false,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            discard_remaining: 0
+        }
+    }
+
+    pub fn set_length_field_length(&mut self, length_field_length: usize) {
+        assert!((1..=8).contains(&length_field_length), "length field must be between 1 and 8 bytes");
+        self.length_field_length = length_field_length;
+    }
+
+    pub fn length_field_length(&self) -> usize {
+        self.length_field_length
+    }
+
+    pub fn set_little_endian(&mut self, little_endian: bool) {
+        self.little_endian = little_endian;
+    }
+
+    pub fn little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    pub fn set_max_frame_length(&mut self, max_frame_length: usize) {
+        self.max_frame_length = max_frame_length;
+    }
+
+    pub fn max_frame_length(&self) -> usize {
+        self.max_frame_length
+    }
+
+    fn encode_frame(&mut self, frame: &[u8], dst: &mut Vec<u8>) -> IOResult<()> {
+        let n = self.length_field_length;
+        if frame.len() > self.max_frame_length || (n < 8 && frame.len() as u64 >= 1u64 << (n * 8)) {
+            return Err(IOError::new(ErrorKind::InvalidInput, "frame exceeds maximum length"));
+        }
+
+        let len = frame.len() as u64;
+        if self.little_endian {
+            dst.extend_from_slice(&len.to_le_bytes()[..n]);
+        } else {
+            dst.extend_from_slice(&len.to_be_bytes()[8 - n..]);
+        }
+        dst.extend_from_slice(frame);
+        Ok(())
+    }
+}
+
+impl Decoder for LengthDelimitedCodec {
+    type Item = Vec<u8>;
+
+    fn decode(&mut self, src: &mut Vec<u8>) -> IOResult<Option<Vec<u8>>> {
+        if self.discard_remaining > 0 {
+            let discarded = self.discard_remaining.min(src.len() as u64);
+            src.drain(..discarded as usize);
+            self.discard_remaining -= discarded;
+            if self.discard_remaining > 0 {
+                return Ok(None);
+            }
+        }
+
+        let n = self.length_field_length;
+        if src.len() < n {
+            return Ok(None);
+        }
+
+        let mut len_bytes = [0u8; 8];
+        let len = if self.little_endian {
+            len_bytes[..n].copy_from_slice(&src[..n]);
+            u64::from_le_bytes(len_bytes)
+        } else {
+            len_bytes[8 - n..].copy_from_slice(&src[..n]);
+            u64::from_be_bytes(len_bytes)
+        };
+
+        if len > self.max_frame_length as u64 {
+            self.discard_remaining = (n as u64).saturating_add(len);
+            return Err(IOError::new(ErrorKind::InvalidData, "frame exceeds maximum length"));
+        }
+
+        let len = len as usize;
+        if src.len() < n + len {
+            src.reserve(n + len - src.len());
+            return Ok(None);
+        }
+
+        let frame = src[n..n + len].to_vec();
+        src.drain(..n + len);
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder<Vec<u8>> for LengthDelimitedCodec {
+    fn encode(&mut self, item: Vec<u8>, dst: &mut Vec<u8>) -> IOResult<()> {
+        self.encode_frame(&item, dst)
+    }
+}
+
+impl Encoder<&[u8]> for LengthDelimitedCodec {
+    fn encode(&mut self, item: &[u8], dst: &mut Vec<u8>) -> IOResult<()> {
+        self.encode_frame(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+
+    use super::*;
+    use crate::block_on;
+    use crate::io::{duplex, AsyncWriteExt, DuplexStream};
+    use crate::stream::{SinkExt, StreamExt};
+
+    async fn is_pending<C: Decoder + Unpin>(framed: &mut Framed<DuplexStream, C>) -> bool {
+        poll_fn(|cx| Poll::Ready(Pin::new(&mut *framed).poll_next(cx).is_pending())).await
+    }
+
+    #[test]
+    fn lines_round_trip() {
+        block_on(async {
+            let (client, server) = duplex(64);
+            let mut writer = Framed::new(client, LinesCodec::new());
+            let mut reader = Framed::new(server, LinesCodec::new());
+
+            writer.send("hello").await.unwrap();
+            writer.send(String::from("world")).await.unwrap();
+            writer.close().await.unwrap();
+
+            assert_eq!(reader.next().await.unwrap().unwrap(), "hello");
+            assert_eq!(reader.next().await.unwrap().unwrap(), "world");
+            assert!(reader.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn lines_reassemble_partial_frames() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut reader = Framed::new(server, LinesCodec::new());
+
+            client.write_all(b"par").await.unwrap();
+            assert!(is_pending(&mut reader).await);
+            assert_eq!(reader.read_buffer(), b"par");
+
+            client.write_all(b"tial\r\nnext").await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), "partial");
+            assert!(is_pending(&mut reader).await);
+
+            client.shutdown().await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), "next");
+            assert!(reader.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn lines_discard_overlong_line() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut reader = Framed::new(server, LinesCodec::new_with_max_length(4));
+
+            client.write_all(b"ok\n0123456").await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), "ok");
+            assert_eq!(reader.next().await.unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+
+            client.write_all(b"789\nfine\ntoolong").await.unwrap();
+            client.shutdown().await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), "fine");
+            assert_eq!(reader.next().await.unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+            assert!(reader.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn length_delimited_round_trip() {
+        block_on(async {
+            let (client, server) = duplex(4096);
+            let mut codec = LengthDelimitedCodec::new();
+            codec.set_length_field_length(2);
+            codec.set_little_endian(true);
+            let mut writer = Framed::new(client, codec.clone());
+            let mut reader = Framed::new(server, codec);
+
+            let big = vec![7u8; 1000];
+            writer.send(&b"abc"[..]).await.unwrap();
+            writer.send(Vec::new()).await.unwrap();
+            writer.send(big.clone()).await.unwrap();
+            assert!(writer.write_buffer().is_empty());
+
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"abc");
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"");
+            assert_eq!(reader.next().await.unwrap().unwrap(), big);
+        });
+    }
+
+    #[test]
+    fn length_delimited_reassembles_partial_frames() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut reader = Framed::new(server, LengthDelimitedCodec::new());
+
+            client.write_all(&[0, 0]).await.unwrap();
+            assert!(is_pending(&mut reader).await);
+            client.write_all(&[0, 5, b'h', b'e']).await.unwrap();
+            assert!(is_pending(&mut reader).await);
+            client.write_all(b"llo").await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"hello");
+        });
+    }
+
+    #[test]
+    fn length_delimited_rejects_length_overflow() {
+        let mut codec = LengthDelimitedCodec::new();
+        codec.set_length_field_length(1);
+        let mut dst = Vec::new();
+        assert_eq!(codec.encode(vec![0u8; 256], &mut dst).unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(dst.is_empty());
+        codec.encode(vec![0u8; 255], &mut dst).unwrap();
+        assert_eq!(dst[0], 255);
+
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut codec = LengthDelimitedCodec::new();
+            codec.set_max_frame_length(4);
+            let mut reader = Framed::new(server, codec);
+
+            client.write_all(&[0xff, 0xff, 0xff, 0xff]).await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+
+            let mut frames = Vec::new();
+            frames.extend_from_slice(&[0, 0, 0, 6]);
+            frames.extend_from_slice(b"oversz");
+            frames.extend_from_slice(&[0, 0, 0, 2]);
+            frames.extend_from_slice(b"ok");
+            let (mut client, server) = duplex(64);
+            let mut codec = LengthDelimitedCodec::new();
+            codec.set_max_frame_length(4);
+            let mut reader = Framed::new(server, codec);
+
+            client.write_all(&frames[..7]).await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap_err().kind(), ErrorKind::InvalidData);
+            client.write_all(&frames[7..]).await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"ok");
+        });
+    }
+
+    #[test]
+    fn length_delimited_eof_mid_frame() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut reader = Framed::new(server, LengthDelimitedCodec::new());
+
+            client.write_all(&[0, 0, 0, 2, b'o', b'k', 0, 0, 0, 9, b'x']).await.unwrap();
+            client.shutdown().await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"ok");
+            assert_eq!(reader.next().await.unwrap().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+            assert!(reader.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        block_on(async {
+            let (client, server) = duplex(64);
+            let mut writer = Framed::new(client, BytesCodec::new());
+            let mut reader = Framed::new(server, BytesCodec::new());
+
+            writer.send(&b"raw bytes"[..]).await.unwrap();
+            writer.close().await.unwrap();
+            assert_eq!(reader.next().await.unwrap().unwrap(), b"raw bytes");
+            assert!(reader.next().await.is_none());
+        });
+    }
+}
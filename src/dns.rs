@@ -0,0 +1,241 @@
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+pub(crate) const TYPE_A: u16 = 1;
+pub(crate) const TYPE_AAAA: u16 = 28;
+const CLASS_IN: u16 = 1;
+
+const FLAG_RESPONSE: u16 = 0x8000;
+const FLAG_TRUNCATED: u16 = 0x0200;
+const FLAG_RECURSION_DESIRED: u16 = 0x0100;
+const RCODE_MASK: u16 = 0x000f;
+const RCODE_NXDOMAIN: u16 = 3;
+
+const MAX_POINTER_JUMPS: usize = 16;
+
+pub(crate) fn build_query(id: u16, name: &str, qtype: u16) -> IOResult<Vec<u8>> {
+    let mut query = Vec::with_capacity(name.len() + 18);
+    query.extend_from_slice(&id.to_be_bytes());
+    query.extend_from_slice(&FLAG_RECURSION_DESIRED.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());
+    query.extend_from_slice(&[0; 6]);
+
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err(IOError::new(ErrorKind::InvalidInput, format!("invalid domain name: {}", name)));
+        }
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+
+    if query.len() > 12 + 255 {
+        return Err(IOError::new(ErrorKind::InvalidInput, format!("domain name too long: {}", name)));
+    }
+
+    query.extend_from_slice(&qtype.to_be_bytes());
+    query.extend_from_slice(&CLASS_IN.to_be_bytes());
+    Ok(query)
+}
+
+pub(crate) fn response_id(message: &[u8]) -> Option<u16> {
+    Some(u16::from_be_bytes([*message.first()?, *message.get(1)?]))
+}
+
+pub(crate) fn is_truncated(message: &[u8]) -> bool {
+    message.len() >= 4 && u16::from_be_bytes([message[2], message[3]]) & FLAG_TRUNCATED != 0
+}
+
+pub(crate) fn parse_response(message: &[u8], qtype: u16) -> IOResult<Vec<IpAddr>> {
+    let mut reader = Reader { message, pos: 0 };
+    let _id = reader.u16()?;
+    let flags = reader.u16()?;
+    let qdcount = reader.u16()?;
+    let ancount = reader.u16()?;
+    reader.u16()?;
+    reader.u16()?;
+
+    if flags & FLAG_RESPONSE == 0 {
+        return Err(malformed("message is not a response"));
+    }
+
+    if flags & FLAG_TRUNCATED != 0 {
+        return Err(malformed("message is truncated"));
+    }
+
+    match flags & RCODE_MASK {
+        0 => {},
+        RCODE_NXDOMAIN => return Err(IOError::new(ErrorKind::NotFound, "no such host")),
+        rcode => return Err(IOError::other(format!("DNS server returned error code {}", rcode)))
+    }
+
+    for _ in 0..qdcount {
+        reader.skip_name()?;
+        reader.skip(4)?;
+    }
+
+    let mut addrs = Vec::new();
+    for _ in 0..ancount {
+        reader.skip_name()?;
+        let rtype = reader.u16()?;
+        let rclass = reader.u16()?;
+        reader.skip(4)?;
+        let rdlength = reader.u16()? as usize;
+        let rdata = reader.bytes(rdlength)?;
+
+        if rclass != CLASS_IN || rtype != qtype {
+            continue;
+        }
+
+        match (rtype, rdata.len()) {
+            (TYPE_A, 4) => addrs.push(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                addrs.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            },
+            _ => return Err(malformed("address record has unexpected length"))
+        }
+    }
+
+    Ok(addrs)
+}
+
+struct Reader<'a> {
+    message: &'a [u8],
+    pos: usize
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> IOResult<&'a [u8]> {
+        let bytes = self.message.get(self.pos..self.pos + len).ok_or_else(|| malformed("message truncated"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> IOResult<()> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn u16(&mut self) -> IOResult<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn skip_name(&mut self) -> IOResult<()> {
+        for _ in 0..MAX_POINTER_JUMPS * 16 {
+            let len = self.bytes(1)?[0];
+            match len & 0xc0 {
+                0x00 if len == 0 => return Ok(()),
+                0x00 => self.skip(len as usize)?,
+                0xc0 => return self.skip(1),
+                _ => return Err(malformed("invalid label type"))
+            }
+        }
+        Err(malformed("name has too many labels"))
+    }
+}
+
+fn malformed(reason: &str) -> IOError {
+    IOError::new(ErrorKind::InvalidData, format!("malformed DNS response: {}", reason))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    const TYPE_CNAME: u16 = 5;
+
+    pub(crate) fn build_response(query: &[u8], flags: u16, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut response = Vec::new();
+        response.extend_from_slice(&query[..2]);
+        response.extend_from_slice(&(FLAG_RESPONSE | FLAG_RECURSION_DESIRED | flags).to_be_bytes());
+        response.extend_from_slice(&1u16.to_be_bytes());
+        response.extend_from_slice(&(answers.len() as u16).to_be_bytes());
+        response.extend_from_slice(&[0; 4]);
+        response.extend_from_slice(&query[12..]);
+
+        for (rtype, rdata) in answers {
+            response.extend_from_slice(&[0xc0, 12]);
+            response.extend_from_slice(&rtype.to_be_bytes());
+            response.extend_from_slice(&CLASS_IN.to_be_bytes());
+            response.extend_from_slice(&300u32.to_be_bytes());
+            response.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            response.extend_from_slice(rdata);
+        }
+        response
+    }
+
+    pub(crate) fn query_type(query: &[u8]) -> u16 {
+        u16::from_be_bytes([query[query.len() - 4], query[query.len() - 3]])
+    }
+
+    pub(crate) fn cname_rdata() -> Vec<u8> {
+        b"\x05alias\x04test\x00".to_vec()
+    }
+
+    #[test]
+    fn build_query_encodes_labels() {
+        let query = build_query(0x1234, "www.example.com.", TYPE_AAAA).unwrap();
+        assert_eq!(&query[..4], &[0x12, 0x34, 0x01, 0x00]);
+        assert_eq!(&query[4..12], &[0, 1, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(&query[12..29], b"\x03www\x07example\x03com\x00");
+        assert_eq!(query_type(&query), TYPE_AAAA);
+        assert_eq!(response_id(&query), Some(0x1234));
+    }
+
+    #[test]
+    fn build_query_rejects_invalid_names() {
+        assert!(build_query(1, "a..b", TYPE_A).is_err());
+        assert!(build_query(1, &"a".repeat(64), TYPE_A).is_err());
+        assert!(build_query(1, &["a"; 130].join("."), TYPE_A).is_err());
+    }
+
+    #[test]
+    fn parse_response_follows_cname_answers() {
+        let query = build_query(7, "www.test", TYPE_A).unwrap();
+        let response = build_response(&query, 0, &[
+            (TYPE_CNAME, cname_rdata()),
+            (TYPE_A, vec![10, 0, 0, 1]),
+            (TYPE_A, vec![10, 0, 0, 2])
+        ]);
+        let addrs = parse_response(&response, TYPE_A).unwrap();
+        assert_eq!(addrs, vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)), IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+    }
+
+    #[test]
+    fn parse_response_reads_aaaa_records() {
+        let query = build_query(7, "www.test", TYPE_AAAA).unwrap();
+        let response = build_response(&query, 0, &[(TYPE_AAAA, Ipv6Addr::LOCALHOST.octets().to_vec())]);
+        assert_eq!(parse_response(&response, TYPE_AAAA).unwrap(), vec![IpAddr::V6(Ipv6Addr::LOCALHOST)]);
+    }
+
+    #[test]
+    fn parse_response_reports_errors() {
+        let query = build_query(7, "www.test", TYPE_A).unwrap();
+
+        let nxdomain = build_response(&query, RCODE_NXDOMAIN, &[]);
+        assert_eq!(parse_response(&nxdomain, TYPE_A).unwrap_err().kind(), ErrorKind::NotFound);
+
+        let servfail = build_response(&query, 2, &[]);
+        assert_eq!(parse_response(&servfail, TYPE_A).unwrap_err().kind(), ErrorKind::Other);
+
+        assert_eq!(parse_response(&query, TYPE_A).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let mut cut = build_response(&query, 0, &[(TYPE_A, vec![10, 0, 0, 1])]);
+        cut.truncate(cut.len() - 2);
+        assert_eq!(parse_response(&cut, TYPE_A).unwrap_err().kind(), ErrorKind::InvalidData);
+
+        let bad_length = build_response(&query, 0, &[(TYPE_A, vec![10, 0, 0])]);
+        assert_eq!(parse_response(&bad_length, TYPE_A).unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parse_response_rejects_truncated_flag() {
+        let query = build_query(7, "www.test", TYPE_A).unwrap();
+        let response = build_response(&query, FLAG_TRUNCATED, &[(TYPE_A, vec![10, 0, 0, 1])]);
+        assert!(is_truncated(&response));
+        assert_eq!(parse_response(&response, TYPE_A).unwrap_err().kind(), ErrorKind::InvalidData);
+        assert!(!is_truncated(&build_response(&query, 0, &[])));
+    }
+}
@@ -0,0 +1,396 @@
+use std::fs::{self as std_fs, DirEntry, File as StdFile, Metadata};
+use std::future::{poll_fn, Future};
+use std::io::{Read, Result as IOResult, Seek, SeekFrom, Write};
+use std::mem::take;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
+use std::vec::IntoIter;
+
+use crate::blocking::{current_blocking_pool, run_blocking_io, BlockingPool};
+use crate::io::{AsyncRead, AsyncWrite};
+use crate::stream::Stream;
+use crate::task::JoinHandle;
+
+const MAX_BUF_SIZE: usize = 2 * 1024 * 1024;
+
+pub fn read(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<Vec<u8>>> + Send + Sync>> {
+    let path = path.as_ref().to_owned();
+    Box::pin(run_blocking_io(move || std_fs::read(path)))
+}
+
+pub fn read_to_string(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<String>> + Send + Sync>> {
+    let path = path.as_ref().to_owned();
+    Box::pin(run_blocking_io(move || std_fs::read_to_string(path)))
+}
+
+pub fn write(
+    path: impl AsRef<Path>,
+    contents: impl AsRef<[u8]>
+) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send + Sync>> {
+    let path = path.as_ref().to_owned();
+    let contents = contents.as_ref().to_vec();
+    Box::pin(run_blocking_io(move || std_fs::write(path, contents)))
+}
+
+pub fn metadata(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<Metadata>> + Send + Sync>> {
+    let path = path.as_ref().to_owned();
+    Box::pin(run_blocking_io(move || std_fs::metadata(path)))
+}
+
+pub fn read_dir(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<ReadDir>> + Send + Sync>> {
+    let path = path.as_ref().to_owned();
+    Box::pin(async move {
+        let entries = run_blocking_io(move || std_fs::read_dir(path).map(|entries| entries.collect::<Vec<_>>())).await?;
+        Ok(ReadDir { entries: entries.into_iter() })
+    })
+}
+
+pub struct ReadDir {
+    entries: IntoIter<IOResult<DirEntry>>
+}
+
+impl ReadDir {
+    pub fn next_entry(&mut self) -> Option<IOResult<DirEntry>> {
+        self.entries.next()
+    }
+}
+
+impl Stream for ReadDir {
+    type Item = IOResult<DirEntry>;
+
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Poll::Ready(self.get_mut().entries.next())
+    }
+}
+
+#[derive(Default)]
+struct Buf {
+    data: Vec<u8>,
+    pos: usize
+}
+
+enum Operation {
+    Read(IOResult<usize>),
+    Write(IOResult<()>)
+}
+
+enum State {
+    Idle(Buf),
+    Busy(JoinHandle<(Operation, Buf)>)
+}
+
+pub struct File {
+    std: Arc<StdFile>,
+    state: State,
+    blocking_pool: Arc<BlockingPool>
+}
+
+impl File {
+    pub fn open(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<File>> + Send + Sync>> {
+        let path = path.as_ref().to_owned();
+        let blocking_pool = current_blocking_pool();
+        Box::pin(async move {
+            let std = blocking_pool.spawn(move || StdFile::open(path)).await??;
+            Ok(File::with_blocking_pool(std, blocking_pool))
+        })
+    }
+
+    pub fn create(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<File>> + Send + Sync>> {
+        let path = path.as_ref().to_owned();
+        let blocking_pool = current_blocking_pool();
+        Box::pin(async move {
+            let std = blocking_pool.spawn(move || StdFile::create(path)).await??;
+            Ok(File::with_blocking_pool(std, blocking_pool))
+        })
+    }
+
+    pub fn from_std(std: StdFile) -> Self {
+        Self::with_blocking_pool(std, current_blocking_pool())
+    }
+
+    fn with_blocking_pool(std: StdFile, blocking_pool: Arc<BlockingPool>) -> Self {
+        Self { std: Arc::new(std), state: State::Idle(Buf::default()), blocking_pool }
+    }
+
+    pub async fn into_std(mut self) -> IOResult<StdFile> {
+        self.seek(SeekFrom::Current(0)).await?;
+        match Arc::try_unwrap(self.std) {
+            Ok(std) => Ok(std),
+            Err(std) => std.try_clone()
+        }
+    }
+
+    pub async fn metadata(&self) -> IOResult<Metadata> {
+        let std = self.std.clone();
+        self.blocking_pool.spawn(move || std.metadata()).await?
+    }
+
+    pub async fn seek(&mut self, pos: SeekFrom) -> IOResult<u64> {
+        poll_fn(|cx| self.poll_complete(cx)).await?;
+        let pos = match (&mut self.state, pos) {
+            (State::Idle(buf), SeekFrom::Current(offset)) => {
+                let unread = (buf.data.len() - buf.pos) as i64;
+                SeekFrom::Current(offset - unread)
+            },
+            (_, pos) => pos
+        };
+        self.state = State::Idle(Buf::default());
+
+        let std = self.std.clone();
+        self.blocking_pool.spawn(move || (&*std).seek(pos)).await?
+    }
+
+    pub async fn sync_all(&mut self) -> IOResult<()> {
+        poll_fn(|cx| self.poll_complete(cx)).await?;
+        let std = self.std.clone();
+        self.blocking_pool.spawn(move || std.sync_all()).await?
+    }
+
+    pub async fn set_len(&mut self, size: u64) -> IOResult<()> {
+        self.seek(SeekFrom::Current(0)).await?;
+        let std = self.std.clone();
+        self.blocking_pool.spawn(move || std.set_len(size)).await?
+    }
+
+    fn complete(&mut self, operation: Operation, mut buf: Buf) -> IOResult<Option<usize>> {
+        let result = match operation {
+            Operation::Read(Ok(n)) => {
+                buf.data.truncate(n);
+                Ok(Some(n))
+            },
+            Operation::Read(Err(e)) => {
+                buf.data.clear();
+                Err(e)
+            },
+            Operation::Write(result) => {
+                buf.data.clear();
+                result.map(|_| None)
+            }
+        };
+        self.state = State::Idle(buf);
+        result
+    }
+
+    fn poll_complete(&mut self, cx: &mut Context<'_>) -> Poll<IOResult<Option<usize>>> {
+        if let State::Busy(handle) = &mut self.state {
+            let (operation, buf) = match ready!(Pin::new(handle).poll(cx)) {
+                Ok(completed) => completed,
+                Err(e) => {
+                    self.state = State::Idle(Buf::default());
+                    return Poll::Ready(Err(e.into()));
+                }
+            };
+            return Poll::Ready(self.complete(operation, buf));
+        }
+        Poll::Ready(Ok(None))
+    }
+
+    fn poll_read_file(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<IOResult<usize>> {
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        loop {
+            match &mut self.state {
+                State::Idle(buf) if buf.pos < buf.data.len() => {
+                    let n = dst.len().min(buf.data.len() - buf.pos);
+                    dst[..n].copy_from_slice(&buf.data[buf.pos..buf.pos + n]);
+                    buf.pos += n;
+                    return Poll::Ready(Ok(n));
+                },
+                State::Idle(buf) => {
+                    let mut buf = take(buf);
+                    buf.data.resize(dst.len().min(MAX_BUF_SIZE), 0);
+                    buf.pos = 0;
+
+                    let std = self.std.clone();
+                    self.state = State::Busy(self.blocking_pool.spawn(move || {
+                        let result = (&*std).read(&mut buf.data);
+                        (Operation::Read(result), buf)
+                    }));
+                },
+                State::Busy(_) => {
+                    if let Some(0) = ready!(self.poll_complete(cx))? {
+                        return Poll::Ready(Ok(0));
+                    }
+                }
+            }
+        }
+    }
+
+    fn poll_write_file(&mut self, cx: &mut Context<'_>, src: &[u8]) -> Poll<IOResult<usize>> {
+        ready!(self.poll_complete(cx))?;
+        let State::Idle(buf) = &mut self.state else {
+            unreachable!()
+        };
+
+        let unread = (buf.data.len() - buf.pos) as i64;
+        let mut buf = take(buf);
+        let n = src.len().min(MAX_BUF_SIZE);
+        buf.data.clear();
+        buf.data.extend_from_slice(&src[..n]);
+        buf.pos = 0;
+
+        let std = self.std.clone();
+        self.state = State::Busy(self.blocking_pool.spawn(move || {
+            let result = if unread > 0 {
+                (&*std).seek(SeekFrom::Current(-unread)).and_then(|_| (&*std).write_all(&buf.data))
+            } else {
+                (&*std).write_all(&buf.data)
+            };
+            (Operation::Write(result), buf)
+        }));
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncRead for File {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        self.get_mut().poll_read_file(cx, buf)
+    }
+}
+
+impl AsyncWrite for File {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        self.get_mut().poll_write_file(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().poll_complete(cx).map_ok(|_| ())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().poll_complete(cx).map_ok(|_| ())
+    }
+}
+
+impl AsRawFd for File {
+    fn as_raw_fd(&self) -> RawFd {
+        self.std.as_raw_fd()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use crate::block_on;
+    use crate::io::{AsyncReadExt, AsyncWriteExt};
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("slava-fs-{}-{}", std::process::id(), name))
+    }
+
+    fn open_with_read_ahead(path: &Path) -> File {
+        std_fs::write(path, (0..100).collect::<Vec<u8>>()).unwrap();
+        let blocking_pool = BlockingPool::new();
+        blocking_pool.set_max_threads(1);
+        let (gate, gated) = channel::<()>();
+        assert!(blocking_pool.execute(Box::new(move || {
+            let _ = gated.recv();
+        })).is_ok());
+
+        let mut file = File::with_blocking_pool(StdFile::open(path).unwrap(), blocking_pool);
+        block_on(async move {
+            let mut dst = [0u8; 100];
+            let polled = poll_fn(|cx| Poll::Ready(Pin::new(&mut file).poll_read(cx, &mut dst).is_pending())).await;
+            assert!(polled);
+            drop(gate);
+
+            let mut dst = [0u8; 4];
+            file.read_exact(&mut dst).await.unwrap();
+            assert_eq!(dst, [0, 1, 2, 3]);
+            file
+        })
+    }
+
+    #[test]
+    fn seek_after_partial_read_skips_read_ahead() {
+        let path = temp_path("seek");
+        let mut file = open_with_read_ahead(&path);
+        let (pos, dst) = block_on(async move {
+            let pos = file.seek(SeekFrom::Current(2)).await.unwrap();
+            let mut dst = [0u8; 2];
+            file.read_exact(&mut dst).await.unwrap();
+            (pos, dst)
+        });
+        assert_eq!((pos, dst), (6, [6, 7]));
+        std_fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn into_std_rewinds_unread_data() {
+        let path = temp_path("into-std");
+        let file = open_with_read_ahead(&path);
+        let mut std = block_on(file.into_std()).unwrap();
+        assert_eq!(std.stream_position().unwrap(), 4);
+
+        let mut rest = Vec::new();
+        std.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, (4..100).collect::<Vec<u8>>());
+        std_fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_then_read_back() {
+        let path = temp_path("write");
+        let contents = (0..3 * MAX_BUF_SIZE + 7).map(|i| i as u8).collect::<Vec<_>>();
+        let (expected, path1) = (contents.clone(), path.clone());
+        let read_back = block_on(async move {
+            let mut file = File::create(&path1).await.unwrap();
+            file.write_all(&contents).await.unwrap();
+            file.flush().await.unwrap();
+
+            let mut file = File::open(&path1).await.unwrap();
+            let mut read_back = vec![0u8; contents.len()];
+            file.read_exact(&mut read_back).await.unwrap();
+            assert_eq!(file.read(&mut [0u8; 1]).await.unwrap(), 0);
+            read_back
+        });
+        assert!(read_back == expected);
+        std_fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn set_len_truncates_and_extends() {
+        let path = temp_path("set-len");
+        let path1 = path.clone();
+        let lens = block_on(async move {
+            let mut file = File::create(&path1).await.unwrap();
+            file.write_all(b"hello world").await.unwrap();
+            file.set_len(5).await.unwrap();
+            let truncated = file.metadata().await.unwrap().len();
+            file.set_len(8).await.unwrap();
+            (truncated, file.metadata().await.unwrap().len())
+        });
+        assert_eq!(lens, (5, 8));
+        assert_eq!(std_fs::read(&path).unwrap(), b"hello\0\0\0");
+        std_fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn read_dir_lists_entries() {
+        let path = temp_path("dir");
+        std_fs::create_dir(&path).unwrap();
+        std_fs::write(path.join("a"), b"a").unwrap();
+        std_fs::write(path.join("b"), b"b").unwrap();
+
+        let path1 = path.clone();
+        let mut names = block_on(async move {
+            let mut entries = read_dir(path1).await.unwrap();
+            let mut names = Vec::new();
+            while let Some(entry) = entries.next_entry() {
+                names.push(entry.unwrap().file_name().into_string().unwrap());
+            }
+            names
+        });
+        names.sort();
+        assert_eq!(names, ["a", "b"]);
+        std_fs::remove_dir_all(path).unwrap();
+    }
+}
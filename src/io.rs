@@ -0,0 +1,890 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, IoSlice, Result as IOResult};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Waker};
+
+use crate::bufread::DEFAULT_BUF_SIZE;
+use crate::socket::{poll_read_fd, poll_shutdown_fd, poll_write_fd, poll_write_vectored_fd, TcpStream};
+use crate::socket_split::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use crate::unix::UnixStream;
+
+pub trait AsyncRead {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>>;
+}
+
+pub trait AsyncWrite {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>>;
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        let buf = bufs.iter().find(|buf| !buf.is_empty()).map_or(&[][..], |buf| &**buf);
+        self.poll_write(cx, buf)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        false
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>>;
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>>;
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> AsyncRead for &mut R {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        Pin::new(&mut **self).poll_read(cx, buf)
+    }
+}
+
+impl<R: AsyncRead + ?Sized> AsyncRead for Pin<Box<R>> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        self.get_mut().as_mut().poll_read(cx, buf)
+    }
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> AsyncWrite for &mut W {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        Pin::new(&mut **self).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        Pin::new(&mut **self).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Pin::new(&mut **self).poll_shutdown(cx)
+    }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWrite for Pin<Box<W>> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        self.get_mut().as_mut().poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        self.get_mut().as_mut().poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        (**self).is_write_vectored()
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().as_mut().poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.get_mut().as_mut().poll_shutdown(cx)
+    }
+}
+
+pub trait AsyncReadExt: AsyncRead {
+    fn read<'a>(&'a mut self, buf: &'a mut [u8]) -> Read<'a, Self> where Self: Unpin {
+        Read { reader: self, buf }
+    }
+
+    fn read_exact<'a>(&'a mut self, buf: &'a mut [u8]) -> ReadExact<'a, Self> where Self: Unpin {
+        ReadExact { reader: self, buf, bytes_read: 0 }
+    }
+}
+
+impl<R: AsyncRead + ?Sized> AsyncReadExt for R {}
+
+pub trait AsyncWriteExt: AsyncWrite {
+    fn write<'a>(&'a mut self, buf: &'a [u8]) -> Write<'a, Self> where Self: Unpin {
+        Write { writer: self, buf }
+    }
+
+    fn write_all<'a>(&'a mut self, buf: &'a [u8]) -> WriteAll<'a, Self> where Self: Unpin {
+        WriteAll { writer: self, buf }
+    }
+
+    fn flush(&mut self) -> Flush<'_, Self> where Self: Unpin {
+        Flush { writer: self }
+    }
+
+    fn shutdown(&mut self) -> Shutdown<'_, Self> where Self: Unpin {
+        Shutdown { writer: self }
+    }
+}
+
+impl<W: AsyncWrite + ?Sized> AsyncWriteExt for W {}
+
+pub struct Read<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8]
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for Read<'_, R> {
+    type Output = IOResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.reader).poll_read(cx, this.buf)
+    }
+}
+
+pub struct ReadExact<'a, R: ?Sized> {
+    reader: &'a mut R,
+    buf: &'a mut [u8],
+    bytes_read: usize
+}
+
+impl<R: AsyncRead + Unpin + ?Sized> Future for ReadExact<'_, R> {
+    type Output = IOResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while this.bytes_read < this.buf.len() {
+            match Pin::new(&mut *this.reader).poll_read(cx, &mut this.buf[this.bytes_read..]) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(IOError::new(
+                    ErrorKind::UnexpectedEof,
+                    "stream closed before enough bytes were read"
+                ))),
+                Poll::Ready(Ok(n)) => this.bytes_read += n,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct Write<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8]
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Write<'_, W> {
+    type Output = IOResult<usize>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        Pin::new(&mut *this.writer).poll_write(cx, this.buf)
+    }
+}
+
+pub struct WriteAll<'a, W: ?Sized> {
+    writer: &'a mut W,
+    buf: &'a [u8]
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for WriteAll<'_, W> {
+    type Output = IOResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        while !this.buf.is_empty() {
+            match Pin::new(&mut *this.writer).poll_write(cx, this.buf) {
+                Poll::Ready(Ok(0)) => return Poll::Ready(Err(IOError::new(
+                    ErrorKind::WriteZero,
+                    "failed to write whole buffer"
+                ))),
+                Poll::Ready(Ok(n)) => this.buf = &this.buf[n..],
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct Flush<'a, W: ?Sized> {
+    writer: &'a mut W
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Flush<'_, W> {
+    type Output = IOResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.writer).poll_flush(cx)
+    }
+}
+
+pub struct Shutdown<'a, W: ?Sized> {
+    writer: &'a mut W
+}
+
+impl<W: AsyncWrite + Unpin + ?Sized> Future for Shutdown<'_, W> {
+    type Output = IOResult<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.writer).poll_shutdown(cx)
+    }
+}
+
+pub fn copy<'a, R, W>(reader: &'a mut R, writer: &'a mut W) -> Copy<'a, R, W>
+    where R: AsyncRead + Unpin + ?Sized,
+          W: AsyncWrite + Unpin + ?Sized
+{
+    Copy { reader, writer, buf: CopyBuffer::new() }
+}
+
+pub fn copy_bidirectional<'a, A, B>(a: &'a mut A, b: &'a mut B) -> CopyBidirectional<'a, A, B>
+    where A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+          B: AsyncRead + AsyncWrite + Unpin + ?Sized
+{
+    CopyBidirectional {
+        a,
+        b,
+        a_to_b: TransferState::Running(CopyBuffer::new()),
+        b_to_a: TransferState::Running(CopyBuffer::new())
+    }
+}
+
+struct CopyBuffer {
+    buf: Box<[u8]>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    read_done: bool,
+    need_flush: bool
+}
+
+impl CopyBuffer {
+    fn new() -> Self {
+        Self {
+            buf: vec![0; DEFAULT_BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            cap: 0,
+            amt: 0,
+            read_done: false,
+            need_flush: false
+        }
+    }
+
+    fn poll_copy<R, W>(
+        &mut self,
+        cx: &mut Context<'_>,
+        mut reader: Pin<&mut R>,
+        mut writer: Pin<&mut W>
+    ) -> Poll<IOResult<u64>>
+        where R: AsyncRead + ?Sized,
+              W: AsyncWrite + ?Sized
+    {
+        loop {
+            if self.pos == self.cap && !self.read_done {
+                match reader.as_mut().poll_read(cx, &mut self.buf) {
+                    Poll::Ready(Ok(0)) => self.read_done = true,
+                    Poll::Ready(Ok(n)) => {
+                        self.pos = 0;
+                        self.cap = n;
+                    },
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => {
+                        if self.need_flush {
+                            ready!(writer.as_mut().poll_flush(cx))?;
+                            self.need_flush = false;
+                        }
+                        return Poll::Pending;
+                    }
+                }
+            }
+
+            while self.pos < self.cap {
+                let n = ready!(writer.as_mut().poll_write(cx, &self.buf[self.pos..self.cap]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(IOError::new(ErrorKind::WriteZero, "write zero bytes into writer")));
+                }
+                self.pos += n;
+                self.amt += n as u64;
+                self.need_flush = true;
+            }
+
+            if self.read_done {
+                ready!(writer.as_mut().poll_flush(cx))?;
+                self.need_flush = false;
+                return Poll::Ready(Ok(self.amt));
+            }
+        }
+    }
+}
+
+pub struct Copy<'a, R: ?Sized, W: ?Sized> {
+    reader: &'a mut R,
+    writer: &'a mut W,
+    buf: CopyBuffer
+}
+
+impl<R, W> Future for Copy<'_, R, W>
+    where R: AsyncRead + Unpin + ?Sized,
+          W: AsyncWrite + Unpin + ?Sized
+{
+    type Output = IOResult<u64>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.buf.poll_copy(cx, Pin::new(&mut *this.reader), Pin::new(&mut *this.writer))
+    }
+}
+
+enum TransferState {
+    Running(CopyBuffer),
+    ShuttingDown(u64),
+    Done(u64)
+}
+
+fn poll_transfer<R, W>(
+    cx: &mut Context<'_>,
+    state: &mut TransferState,
+    mut reader: Pin<&mut R>,
+    mut writer: Pin<&mut W>
+) -> Poll<IOResult<u64>>
+    where R: AsyncRead + ?Sized,
+          W: AsyncWrite + ?Sized
+{
+    loop {
+        match state {
+            TransferState::Running(buf) => {
+                let count = ready!(buf.poll_copy(cx, reader.as_mut(), writer.as_mut()))?;
+                *state = TransferState::ShuttingDown(count);
+            },
+            TransferState::ShuttingDown(count) => {
+                ready!(writer.as_mut().poll_shutdown(cx))?;
+                *state = TransferState::Done(*count);
+            },
+            TransferState::Done(count) => return Poll::Ready(Ok(*count))
+        }
+    }
+}
+
+pub struct CopyBidirectional<'a, A: ?Sized, B: ?Sized> {
+    a: &'a mut A,
+    b: &'a mut B,
+    a_to_b: TransferState,
+    b_to_a: TransferState
+}
+
+impl<A, B> Future for CopyBidirectional<'_, A, B>
+    where A: AsyncRead + AsyncWrite + Unpin + ?Sized,
+          B: AsyncRead + AsyncWrite + Unpin + ?Sized
+{
+    type Output = IOResult<(u64, u64)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let a_to_b = poll_transfer(cx, &mut this.a_to_b, Pin::new(&mut *this.a), Pin::new(&mut *this.b))?;
+        let b_to_a = poll_transfer(cx, &mut this.b_to_a, Pin::new(&mut *this.b), Pin::new(&mut *this.a))?;
+
+        let (Poll::Ready(a_to_b), Poll::Ready(b_to_a)) = (a_to_b, b_to_a) else {
+            return Poll::Pending;
+        };
+        Poll::Ready(Ok((a_to_b, b_to_a)))
+    }
+}
+
+pub fn duplex(capacity: usize) -> (DuplexStream, DuplexStream) {
+    let one = Arc::new(Mutex::new(Pipe::new(capacity)));
+    let two = Arc::new(Mutex::new(Pipe::new(capacity)));
+
+    (
+        DuplexStream { read: one.clone(), write: two.clone() },
+        DuplexStream { read: two, write: one }
+    )
+}
+
+#[derive(Debug)]
+pub struct DuplexStream {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>
+}
+
+#[derive(Debug)]
+struct Pipe {
+    buffer: VecDeque<u8>,
+    capacity: usize,
+    is_closed: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            capacity: capacity.max(1),
+            is_closed: false,
+            read_waker: None,
+            write_waker: None
+        }
+    }
+
+    fn close(&mut self) {
+        self.is_closed = true;
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        if self.buffer.is_empty() && !buf.is_empty() {
+            if self.is_closed {
+                return Poll::Ready(Ok(0));
+            }
+
+            self.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let n = buf.len().min(self.buffer.len());
+        for (dst, src) in buf.iter_mut().zip(self.buffer.drain(..n)) {
+            *dst = src;
+        }
+        if let Some(waker) = self.write_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        if self.is_closed {
+            return Poll::Ready(Err(IOError::new(ErrorKind::BrokenPipe, "duplex stream closed")));
+        }
+
+        let available = self.capacity - self.buffer.len();
+        if available == 0 && !buf.is_empty() {
+            self.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        let n = buf.len().min(available);
+        self.buffer.extend(&buf[..n]);
+        if let Some(waker) = self.read_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl DuplexStream {
+    pub(crate) fn poll_read_pipe(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        self.read.lock().unwrap().poll_read(cx, buf)
+    }
+
+    pub(crate) fn poll_write_pipe(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        self.write.lock().unwrap().poll_write(cx, buf)
+    }
+
+    pub(crate) fn shutdown_pipe(&self) {
+        self.write.lock().unwrap().close();
+    }
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        self.poll_read_pipe(cx, buf)
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        self.poll_write_pipe(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.shutdown_pipe();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.read.lock().unwrap().close();
+        self.write.lock().unwrap().close();
+    }
+}
+
+impl AsyncRead for TcpStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.fd, cx, buf)
+    }
+}
+
+impl AsyncRead for &TcpStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for TcpStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.fd)
+    }
+}
+
+impl AsyncWrite for &TcpStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.fd)
+    }
+}
+
+impl AsyncRead for UnixStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.fd, cx, buf)
+    }
+}
+
+impl AsyncRead for &UnixStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for UnixStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.fd)
+    }
+}
+
+impl AsyncWrite for &UnixStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.fd)
+    }
+}
+
+impl AsyncRead for ReadHalf<'_> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.stream.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for WriteHalf<'_> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.stream.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.stream.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.stream.fd)
+    }
+}
+
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+        poll_read_fd(self.stream.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.stream.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.stream.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.stream.fd)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::task::Wake;
+
+    use super::*;
+    use crate::block_on;
+
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn counting_waker() -> (Arc<CountingWaker>, Waker) {
+        let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+        (counter.clone(), Waker::from(counter))
+    }
+
+    fn wakes(counter: &CountingWaker) -> usize {
+        counter.0.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn write_blocks_at_capacity_until_peer_reads() {
+        let (mut client, mut server) = duplex(4);
+        let (write_wakes, waker) = counting_waker();
+        let mut cx = Context::from_waker(&waker);
+
+        assert!(matches!(Pin::new(&mut client).poll_write(&mut cx, b"abcdef"), Poll::Ready(Ok(4))));
+        assert!(Pin::new(&mut client).poll_write(&mut cx, b"ef").is_pending());
+        assert_eq!(wakes(&write_wakes), 0);
+
+        let mut buf = [0u8; 2];
+        assert!(matches!(Pin::new(&mut server).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(2))));
+        assert_eq!(&buf, b"ab");
+        assert_eq!(wakes(&write_wakes), 1);
+        assert!(matches!(Pin::new(&mut client).poll_write(&mut cx, b"ef"), Poll::Ready(Ok(2))));
+
+        let mut buf = [0u8; 8];
+        assert!(matches!(Pin::new(&mut server).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(4))));
+        assert_eq!(&buf[..4], b"cdef");
+    }
+
+    #[test]
+    fn read_wakes_when_peer_writes() {
+        let (mut client, mut server) = duplex(16);
+        let (server_wakes, server_waker) = counting_waker();
+        let (client_wakes, client_waker) = counting_waker();
+        let mut buf = [0u8; 8];
+
+        assert!(Pin::new(&mut server).poll_read(&mut Context::from_waker(&server_waker), &mut buf).is_pending());
+        assert!(Pin::new(&mut client).poll_read(&mut Context::from_waker(&client_waker), &mut buf).is_pending());
+
+        let _ = Pin::new(&mut client).poll_write(&mut Context::from_waker(&client_waker), b"ping");
+        assert_eq!((wakes(&server_wakes), wakes(&client_wakes)), (1, 0));
+
+        let _ = Pin::new(&mut server).poll_write(&mut Context::from_waker(&server_waker), b"pong");
+        assert_eq!((wakes(&server_wakes), wakes(&client_wakes)), (1, 1));
+
+        let mut cx = Context::from_waker(&server_waker);
+        assert!(matches!(Pin::new(&mut server).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(4))));
+        assert_eq!(&buf[..4], b"ping");
+        assert!(matches!(Pin::new(&mut client).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(4))));
+        assert_eq!(&buf[..4], b"pong");
+    }
+
+    #[test]
+    fn shutdown_delivers_eof_after_buffered_data() {
+        let (mut client, mut server) = duplex(16);
+        let (read_wakes, waker) = counting_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0u8; 8];
+
+        assert!(Pin::new(&mut server).poll_read(&mut cx, &mut buf).is_pending());
+        let _ = Pin::new(&mut client).poll_write(&mut cx, b"last");
+        assert!(Pin::new(&mut client).poll_shutdown(&mut cx).is_ready());
+        assert_eq!(wakes(&read_wakes), 1);
+
+        assert!(matches!(Pin::new(&mut server).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(4))));
+        assert!(matches!(Pin::new(&mut server).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(0))));
+        assert!(matches!(
+            Pin::new(&mut client).poll_write(&mut cx, b"more"),
+            Poll::Ready(Err(e)) if e.kind() == ErrorKind::BrokenPipe
+        ));
+
+        assert!(matches!(Pin::new(&mut server).poll_write(&mut cx, b"reply"), Poll::Ready(Ok(5))));
+        assert!(matches!(Pin::new(&mut client).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(5))));
+    }
+
+    #[test]
+    fn dropped_peer_breaks_pipe_and_wakes_waiters() {
+        let (mut client, server) = duplex(2);
+        let (wake_count, waker) = counting_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0u8; 8];
+
+        assert!(matches!(Pin::new(&mut client).poll_write(&mut cx, b"xy"), Poll::Ready(Ok(2))));
+        assert!(Pin::new(&mut client).poll_write(&mut cx, b"z").is_pending());
+        assert!(Pin::new(&mut client).poll_read(&mut cx, &mut buf).is_pending());
+
+        drop(server);
+        assert_eq!(wakes(&wake_count), 2);
+        assert!(matches!(
+            Pin::new(&mut client).poll_write(&mut cx, b"z"),
+            Poll::Ready(Err(e)) if e.kind() == ErrorKind::BrokenPipe
+        ));
+        assert!(matches!(Pin::new(&mut client).poll_read(&mut cx, &mut buf), Poll::Ready(Ok(0))));
+    }
+
+    async fn read_to_end(reader: &mut DuplexStream) -> Vec<u8> {
+        let mut received = Vec::new();
+        let mut buf = [0u8; 1024];
+        loop {
+            match reader.read(&mut buf).await.unwrap() {
+                0 => return received,
+                n => received.extend_from_slice(&buf[..n])
+            }
+        }
+    }
+
+    #[test]
+    fn copy_reports_bytes_copied() {
+        let contents = (0..3 * DEFAULT_BUF_SIZE + 5).map(|i| i as u8).collect::<Vec<_>>();
+        let expected = contents.clone();
+        let (copied, received) = block_on(async move {
+            let (mut source, mut reader) = duplex(contents.len());
+            let (mut writer, mut sink) = duplex(contents.len());
+            source.write_all(&contents).await.unwrap();
+            source.shutdown().await.unwrap();
+
+            let copied = copy(&mut reader, &mut writer).await.unwrap();
+            drop(writer);
+            (copied, read_to_end(&mut sink).await)
+        });
+        assert_eq!(copied, expected.len() as u64);
+        assert!(received == expected);
+    }
+
+    #[test]
+    fn copy_bidirectional_propagates_half_close() {
+        block_on(async {
+            let (mut client, mut proxy_client) = duplex(64);
+            let (mut proxy_server, mut server) = duplex(64);
+            client.write_all(b"ping").await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let mut proxy = copy_bidirectional(&mut proxy_client, &mut proxy_server);
+            assert!(poll_fn(|cx| Poll::Ready(Pin::new(&mut proxy).poll(cx).is_pending())).await);
+            assert_eq!(read_to_end(&mut server).await, b"ping");
+
+            server.write_all(b"pong!").await.unwrap();
+            server.shutdown().await.unwrap();
+            assert_eq!(proxy.await.unwrap(), (4, 5));
+            assert_eq!(read_to_end(&mut client).await, b"pong!");
+        });
+    }
+
+    #[test]
+    fn read_exact_reports_short_read() {
+        block_on(async {
+            let (mut client, mut server) = duplex(64);
+            client.write_all(b"abc").await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let mut buf = [0u8; 5];
+            assert_eq!(server.read_exact(&mut buf).await.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+            assert_eq!(&buf[..3], b"abc");
+        });
+    }
+}
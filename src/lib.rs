@@ -1,26 +1,61 @@
 pub mod socket;
 pub mod socket_tokio;
+pub mod socket_split;
+pub mod socket_zerocopy;
 pub mod bufread;
+pub mod bufwrite;
+pub mod sockopt;
+pub mod time;
+pub mod udp;
+pub mod unix;
+pub mod stream;
+pub mod server;
+pub mod task;
+pub mod fs;
+pub mod net;
+pub mod io;
+pub mod codec;
 
-use std::future::Future;
+mod blocking;
+mod dns;
+
+use std::cell::RefCell;
+use std::future::{poll_fn, Future};
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use std::pin::Pin;
-use std::sync::{Arc, Mutex};
-use std::task::{Context, RawWaker, RawWakerVTable, Waker};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
 use std::thread::spawn as thread_spawn;
+use std::time::Duration;
+
+use crossbeam::channel::{unbounded as channel_unbounded, Receiver, Select, Sender};
+
+use crate::blocking::{set_current_blocking_pool, BlockingPool};
+use crate::task::{join_channel, JoinHandle};
 
-use crossbeam::channel::{unbounded as channel_unbounded, Sender, Receiver};
+const MAX_PINNED_QUEUES_BEFORE_RUN: usize = 256;
 
 pub type TaskFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
 
 pub struct Slava {
     scheduled: Receiver<SlavaTask>,
     sender: Sender<SlavaTask>,
+    pinned: Mutex<PinnedQueues>,
+    blocking_pool: Arc<BlockingPool>
+}
+
+struct PinnedQueues {
+    n_worker_thread: usize,
+    queues: Vec<(Sender<SlavaTask>, Receiver<SlavaTask>)>
 }
 
 impl Slava {
+    #[allow(clippy::self_named_constructors)]
     pub fn slava() -> Arc<Self> {
         let (sender, scheduled) = channel_unbounded();
-        Arc::new(Self { scheduled, sender })
+        let pinned = Mutex::new(PinnedQueues { n_worker_thread: 0, queues: Vec::new() });
+        Arc::new(Self { scheduled, sender, pinned, blocking_pool: BlockingPool::new() })
     }
 
     pub fn spawn(&self, task_fut: impl Future<Output = ()> + Send + 'static) {
@@ -28,19 +63,76 @@ impl Slava {
         self.sender.send(task).unwrap();
     }
 
+    pub fn spawn_with_handle<T: Send + 'static>(
+        &self,
+        task_fut: impl Future<Output = T> + Send + 'static
+    ) -> JoinHandle<T> {
+        let (sender, handle) = join_channel();
+        let mut sender = Some(sender);
+        let mut task_fut = Box::pin(task_fut);
+        self.spawn(poll_fn(move |cx| {
+            match catch_unwind(AssertUnwindSafe(|| task_fut.as_mut().poll(cx))) {
+                Ok(Poll::Pending) => return Poll::Pending,
+                Ok(Poll::Ready(output)) => sender.take().unwrap().send(output),
+                Err(panic) => sender.take().unwrap().send_panic(panic)
+            }
+            Poll::Ready(())
+        }));
+        handle
+    }
+
+    pub fn spawn_blocking<T, F>(&self, f: F) -> JoinHandle<T>
+        where T: Send + 'static,
+              F: FnOnce() -> T + Send + 'static
+    {
+        self.blocking_pool.spawn(f)
+    }
+
+    pub fn set_max_blocking_threads(&self, max_threads: usize) {
+        self.blocking_pool.set_max_threads(max_threads);
+    }
+
+    pub fn set_blocking_keep_alive(&self, keep_alive: Duration) {
+        self.blocking_pool.set_keep_alive(keep_alive);
+    }
+
+    pub fn spawn_pinned(&self, worker: usize, task_fut: impl Future<Output = ()> + Send + 'static) {
+        let mut pinned = self.pinned.lock().unwrap();
+        let n_queues = if pinned.n_worker_thread == 0 { MAX_PINNED_QUEUES_BEFORE_RUN } else { pinned.n_worker_thread };
+        let worker = worker % n_queues;
+        while pinned.queues.len() <= worker {
+            pinned.queues.push(channel_unbounded());
+        }
+
+        let sender = pinned.queues[worker].0.clone();
+        drop(pinned);
+
+        let task = SlavaTask::new(sender.clone(), Box::pin(task_fut));
+        sender.send(task).unwrap();
+    }
+
     pub fn run(&self, n_worker_thread: usize) {
         let mut join_handles = Vec::new();
+        let n_worker_thread = n_worker_thread.max(1);
+
+        let mut pinned = self.pinned.lock().unwrap();
+        pinned.n_worker_thread = n_worker_thread;
+        while pinned.queues.len() < n_worker_thread {
+            pinned.queues.push(channel_unbounded());
+        }
 
-        for _ in 0..n_worker_thread {
-            let scheduled = self.scheduled.clone();
-            join_handles.push(thread_spawn(move || {
-                while let Ok(task) = scheduled.recv() {
-                    let waker = task.make_waker();
-                    let mut cx = Context::from_waker(&waker);
-                    let _ = task.task_fut.lock().unwrap().as_mut().poll(&mut cx);
+        for worker in 0..n_worker_thread {
+            let mut receivers = vec![self.scheduled.clone()];
+            for (idx, (_, receiver)) in pinned.queues.iter().enumerate() {
+                if idx % n_worker_thread == worker {
+                    receivers.push(receiver.clone());
                 }
-            }));
+            }
+
+            let blocking_pool = self.blocking_pool.clone();
+            join_handles.push(thread_spawn(move || run_worker(receivers, blocking_pool, None)));
         }
+        drop(pinned);
 
         for handle in join_handles {
             let _ = handle.join();
@@ -48,25 +140,134 @@ impl Slava {
     }
 
     pub fn run_singlethreaded(&self) {
-        while let Ok(task) = self.scheduled.recv() {
-            let waker = task.make_waker();
-            let mut cx = Context::from_waker(&waker);
-            let _ = task.task_fut.lock().unwrap().as_mut().poll(&mut cx);
+        let mut pinned = self.pinned.lock().unwrap();
+        pinned.n_worker_thread = 1;
+        if pinned.queues.is_empty() {
+            pinned.queues.push(channel_unbounded());
+        }
+
+        let mut receivers = vec![self.scheduled.clone()];
+        receivers.extend(pinned.queues.iter().map(|(_, receiver)| receiver.clone()));
+        drop(pinned);
+
+        run_worker(receivers, self.blocking_pool.clone(), None);
+    }
+}
+
+impl Drop for Slava {
+    fn drop(&mut self) {
+        self.blocking_pool.shutdown();
+    }
+}
+
+struct WorkerContext {
+    receivers: Vec<Receiver<SlavaTask>>,
+    blocking_pool: Arc<BlockingPool>
+}
+
+thread_local! {
+    static CURRENT_WORKER: RefCell<Option<WorkerContext>> = const { RefCell::new(None) };
+}
+
+fn run_worker(
+    receivers: Vec<Receiver<SlavaTask>>,
+    blocking_pool: Arc<BlockingPool>,
+    stop: Option<Receiver<()>>
+) {
+    set_current_blocking_pool(blocking_pool.clone());
+    CURRENT_WORKER.with(|current| {
+        *current.borrow_mut() = Some(WorkerContext { receivers: receivers.clone(), blocking_pool })
+    });
+
+    let mut select = Select::new();
+    for receiver in receivers.iter() {
+        select.recv(receiver);
+    }
+    if let Some(stop) = &stop {
+        select.recv(stop);
+    }
+
+    loop {
+        let operation = select.select();
+        let idx = operation.index();
+        if idx == receivers.len() {
+            let _ = operation.recv(stop.as_ref().unwrap());
+            CURRENT_WORKER.with(|current| *current.borrow_mut() = None);
+            return;
         }
+
+        let Ok(task) = operation.recv(&receivers[idx]) else {
+            return;
+        };
+        task.run();
     }
 }
 
+fn block_in_place<F, R>(f: F) -> R
+    where F: FnOnce() -> R
+{
+    let context = CURRENT_WORKER.with(|current| {
+        current.borrow().as_ref().map(|context| (context.receivers.clone(), context.blocking_pool.clone()))
+    });
+    let Some((receivers, blocking_pool)) = context else {
+        return f();
+    };
+
+    let (stop_sender, stop) = channel_unbounded();
+    let replacement_pool = blocking_pool.clone();
+    if replacement_pool.execute(Box::new(move || run_worker(receivers, blocking_pool, Some(stop)))).is_err() {
+        return f();
+    }
+
+    let result = f();
+    drop(stop_sender);
+    result
+}
+
 #[derive(Clone)]
 struct SlavaTask {
     sender: Sender<SlavaTask>,
-    task_fut: Arc<Mutex<TaskFuture>>
+    task_fut: Arc<Mutex<Option<TaskFuture>>>,
+    notified: Arc<AtomicBool>
 }
 
 impl SlavaTask {
     pub fn new(sender: Sender<SlavaTask>, task_fut: TaskFuture) -> Self {
         Self {
             sender,
-            task_fut: Arc::new(Mutex::new(task_fut))
+            task_fut: Arc::new(Mutex::new(Some(task_fut))),
+            notified: Arc::new(AtomicBool::new(false))
+        }
+    }
+
+    fn lock_for_poll(&self) -> Option<MutexGuard<'_, Option<TaskFuture>>> {
+        if let Ok(task_fut) = self.task_fut.try_lock() {
+            return Some(task_fut);
+        }
+
+        self.notified.store(true, Ordering::SeqCst);
+        let task_fut = self.task_fut.try_lock().ok()?;
+        self.notified.store(false, Ordering::SeqCst);
+        Some(task_fut)
+    }
+
+    fn run(&self) {
+        let Some(mut task_fut) = self.lock_for_poll() else {
+            return;
+        };
+
+        let waker = self.make_waker();
+        let mut cx = Context::from_waker(&waker);
+        if let Some(fut) = task_fut.as_mut() {
+            match catch_unwind(AssertUnwindSafe(|| fut.as_mut().poll(&mut cx))) {
+                Ok(Poll::Pending) => {},
+                Ok(Poll::Ready(())) | Err(_) => *task_fut = None
+            }
+        }
+        drop(task_fut);
+
+        if self.notified.swap(false, Ordering::SeqCst) {
+            let _ = self.sender.send(self.clone());
         }
     }
 
@@ -108,3 +309,70 @@ pub const SLAVA_WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(
     SlavaTask::wake_by_ref_raw,
     SlavaTask::drop_raw
 );
+
+#[cfg(test)]
+pub(crate) fn block_on<T: Send + 'static>(fut: impl Future<Output = T> + Send + 'static) -> T {
+    let slava = Slava::slava();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    slava.spawn(async move {
+        let _ = sender.send(fut.await);
+    });
+
+    let (stop_sender, stop) = channel_unbounded();
+    let receivers = vec![slava.scheduled.clone()];
+    let blocking_pool = slava.blocking_pool.clone();
+    let worker = thread_spawn(move || run_worker(receivers, blocking_pool, Some(stop)));
+
+    let result = receiver.recv();
+    drop(stop_sender);
+    worker.join().unwrap();
+    result.unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+
+    #[test]
+    fn spawn_pinned_bounds_queues_before_run() {
+        let slava = Slava::slava();
+        let (sender, receiver) = channel();
+        let sender1 = sender.clone();
+        slava.spawn_pinned(usize::MAX, async move {
+            let _ = sender1.send(1);
+        });
+        assert!(slava.pinned.lock().unwrap().queues.len() <= MAX_PINNED_QUEUES_BEFORE_RUN);
+
+        let runtime = slava.clone();
+        thread_spawn(move || runtime.run(2));
+        assert_eq!(receiver.recv().unwrap(), 1);
+
+        slava.spawn_pinned(usize::MAX, async move {
+            let _ = sender.send(2);
+        });
+        assert_eq!(receiver.recv().unwrap(), 2);
+        assert!(slava.pinned.lock().unwrap().queues.len() <= MAX_PINNED_QUEUES_BEFORE_RUN);
+    }
+
+    #[test]
+    fn block_in_place_runs_inline_after_blocking_pool_shutdown() {
+        let slava = Slava::slava();
+        slava.blocking_pool.shutdown();
+        let (sender, receiver) = channel();
+        let sender1 = sender.clone();
+        slava.spawn(async move {
+            let _ = sender1.send(crate::task::block_in_place(|| 1));
+        });
+        slava.spawn(async move {
+            let _ = sender.send(2);
+        });
+
+        let runtime = slava.clone();
+        thread_spawn(move || runtime.run(1));
+        let mut results = [receiver.recv().unwrap(), receiver.recv().unwrap()];
+        results.sort();
+        assert_eq!(results, [1, 2]);
+    }
+}
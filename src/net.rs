@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+use std::collections::hash_map::RandomState;
+use std::fs::read_to_string;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs};
+use std::pin::Pin;
+use std::time::Duration;
+
+use crate::blocking::run_blocking_io;
+use crate::dns;
+use crate::io::{AsyncReadExt, AsyncWriteExt};
+use crate::socket::TcpStream;
+use crate::time::timeout;
+use crate::udp::UdpSocket;
+
+pub const DNS_PORT: u16 = 53;
+pub const DEFAULT_RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+pub const DEFAULT_RESOLVE_ATTEMPTS: usize = 2;
+
+const MAX_DNS_MESSAGE: usize = 4096;
+
+pub fn lookup_host(host: &str) -> Pin<Box<dyn Future<Output=IOResult<Vec<SocketAddr>>> + Send + Sync>> {
+    let host = host.to_string();
+    Box::pin(run_blocking_io(move || host.to_socket_addrs().map(|addrs| addrs.collect())))
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolverConfig {
+    pub nameservers: Vec<SocketAddr>,
+    pub hosts: HashMap<String, Vec<IpAddr>>,
+    pub timeout: Duration,
+    pub attempts: usize
+}
+
+impl Default for ResolverConfig {
+    fn default() -> Self {
+        Self {
+            nameservers: Vec::new(),
+            hosts: HashMap::new(),
+            timeout: DEFAULT_RESOLVE_TIMEOUT,
+            attempts: DEFAULT_RESOLVE_ATTEMPTS
+        }
+    }
+}
+
+impl ResolverConfig {
+    pub fn from_system_conf() -> IOResult<Self> {
+        let mut config = Self::default();
+        config.parse_resolv_conf(&read_to_string("/etc/resolv.conf")?);
+        if let Ok(hosts) = read_to_string("/etc/hosts") {
+            config.parse_hosts(&hosts);
+        }
+
+        if config.nameservers.is_empty() {
+            config.nameservers.push(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DNS_PORT));
+        }
+        Ok(config)
+    }
+
+    pub fn parse_resolv_conf(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let mut words = strip_comment(line).split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    if let Some(Ok(ip)) = words.next().map(str::parse::<IpAddr>) {
+                        self.nameservers.push(SocketAddr::new(ip, DNS_PORT));
+                    }
+                },
+                Some("options") => for option in words {
+                    if let Some(Ok(secs)) = option.strip_prefix("timeout:").map(str::parse::<u64>) {
+                        self.timeout = Duration::from_secs(secs.max(1));
+                    } else if let Some(Ok(attempts)) = option.strip_prefix("attempts:").map(str::parse::<usize>) {
+                        self.attempts = attempts.max(1);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+
+    pub fn parse_hosts(&mut self, contents: &str) {
+        for line in contents.lines() {
+            let mut words = strip_comment(line).split_whitespace();
+            let Some(Ok(ip)) = words.next().map(str::parse::<IpAddr>) else {
+                continue;
+            };
+
+            for name in words {
+                let addrs = self.hosts.entry(name.to_ascii_lowercase()).or_default();
+                if !addrs.contains(&ip) {
+                    addrs.push(ip);
+                }
+            }
+        }
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split(['#', ';']).next().unwrap_or("")
+}
+
+pub struct Resolver {
+    config: ResolverConfig
+}
+
+impl Resolver {
+    pub fn new(config: ResolverConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn from_system_conf() -> IOResult<Self> {
+        Ok(Self::new(ResolverConfig::from_system_conf()?))
+    }
+
+    pub fn config(&self) -> &ResolverConfig {
+        &self.config
+    }
+
+    pub fn lookup_host<'a>(
+        &'a self,
+        name: &'a str,
+        port: u16
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<Vec<SocketAddr>>> + Send + Sync>> {
+        Box::pin(async move {
+            let addrs = self.lookup_ip(name).await?;
+            Ok(addrs.into_iter().map(|ip| SocketAddr::new(ip, port)).collect())
+        })
+    }
+
+    pub fn lookup_ip<'a>(&'a self, name: &'a str) -> Pin<Box<dyn 'a + Future<Output=IOResult<Vec<IpAddr>>> + Send + Sync>> {
+        Box::pin(async move {
+            if let Ok(ip) = name.parse::<IpAddr>() {
+                return Ok(vec![ip]);
+            }
+
+            let name = name.trim_end_matches('.').to_ascii_lowercase();
+            if let Some(addrs) = self.config.hosts.get(&name) {
+                return Ok(addrs.clone());
+            }
+
+            let mut addrs = self.query(&name, dns::TYPE_A).await?;
+            match self.query(&name, dns::TYPE_AAAA).await {
+                Ok(v6_addrs) => addrs.extend(v6_addrs),
+                Err(e) if addrs.is_empty() => return Err(e),
+                Err(_) => {}
+            }
+
+            if addrs.is_empty() {
+                return Err(IOError::new(ErrorKind::NotFound, format!("no addresses found for {}", name)));
+            }
+            Ok(addrs)
+        })
+    }
+
+    async fn query(&self, name: &str, qtype: u16) -> IOResult<Vec<IpAddr>> {
+        let mut last_error = IOError::new(ErrorKind::NotFound, "no nameservers configured");
+        for _ in 0..self.config.attempts.max(1) {
+            for nameserver in self.config.nameservers.iter() {
+                match self.query_nameserver(*nameserver, name, qtype).await {
+                    Ok(addrs) => return Ok(addrs),
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Err(e),
+                    Err(e) => last_error = e
+                }
+            }
+        }
+        Err(last_error)
+    }
+
+    async fn query_nameserver(&self, nameserver: SocketAddr, name: &str, qtype: u16) -> IOResult<Vec<IpAddr>> {
+        let id = RandomState::new().hash_one((name, qtype)) as u16;
+        let query = dns::build_query(id, name, qtype)?;
+
+        let local_addr = match nameserver {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)
+        };
+        let socket = UdpSocket::bind(local_addr)?;
+        socket.connect(nameserver)?;
+        socket.send(&query).await?;
+
+        let mut buf = vec![0u8; MAX_DNS_MESSAGE];
+        let n = timeout(self.config.timeout, async {
+            loop {
+                let n = socket.recv(&mut buf).await?;
+                if dns::response_id(&buf[..n]) == Some(id) {
+                    return IOResult::Ok(n);
+                }
+            }
+        }).await??;
+
+        if dns::is_truncated(&buf[..n]) {
+            return timeout(self.config.timeout, query_nameserver_tcp(nameserver, &query, id, qtype)).await?;
+        }
+        dns::parse_response(&buf[..n], qtype)
+    }
+}
+
+async fn query_nameserver_tcp(nameserver: SocketAddr, query: &[u8], id: u16, qtype: u16) -> IOResult<Vec<IpAddr>> {
+    let mut stream = TcpStream::connect(nameserver).await?;
+    let mut message = Vec::with_capacity(query.len() + 2);
+    message.extend_from_slice(&(query.len() as u16).to_be_bytes());
+    message.extend_from_slice(query);
+    stream.write_all(&message).await?;
+
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await?;
+    let mut response = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut response).await?;
+
+    if dns::response_id(&response) != Some(id) {
+        return Err(IOError::new(ErrorKind::InvalidData, "DNS response id does not match query"));
+    }
+    dns::parse_response(&response, qtype)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::{TcpListener as StdTcpListener, UdpSocket as StdUdpSocket};
+    use std::thread::spawn as thread_spawn;
+
+    use super::*;
+    use crate::block_on;
+    use crate::dns::tests::{build_response, cname_rdata, query_type};
+
+    const FLAG_TRUNCATED: u16 = 0x0200;
+    const RCODE_NXDOMAIN: u16 = 3;
+
+    fn query_name(query: &[u8]) -> String {
+        let mut labels = Vec::new();
+        let mut pos = 12;
+        while query[pos] != 0 {
+            let len = query[pos] as usize;
+            labels.push(String::from_utf8_lossy(&query[pos + 1..pos + 1 + len]).into_owned());
+            pos += len + 1;
+        }
+        labels.join(".")
+    }
+
+    fn answer(query: &[u8], over_tcp: bool) -> Vec<u8> {
+        let qtype = query_type(query);
+        match (query_name(query).as_str(), qtype) {
+            ("www.test", dns::TYPE_A) => build_response(query, 0, &[(5, cname_rdata()), (qtype, vec![10, 0, 0, 1])]),
+            ("www.test", _) => build_response(query, 0, &[(qtype, Ipv6Addr::LOCALHOST.octets().to_vec())]),
+            ("v4only.test", dns::TYPE_A) => build_response(query, 0, &[(qtype, vec![10, 0, 0, 2])]),
+            ("big.test", dns::TYPE_A) if over_tcp => build_response(query, 0, &[(qtype, vec![10, 0, 0, 3])]),
+            ("big.test", dns::TYPE_A) => build_response(query, FLAG_TRUNCATED, &[]),
+            ("missing.test", _) => build_response(query, RCODE_NXDOMAIN, &[]),
+            _ => build_response(query, 0, &[])
+        }
+    }
+
+    fn stub_server() -> SocketAddr {
+        let (udp, tcp) = loop {
+            let udp = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+            if let Ok(tcp) = StdTcpListener::bind(udp.local_addr().unwrap()) {
+                break (udp, tcp);
+            }
+        };
+        let addr = udp.local_addr().unwrap();
+
+        thread_spawn(move || {
+            let mut buf = [0u8; 512];
+            while let Ok((n, peer)) = udp.recv_from(&mut buf) {
+                let query = &buf[..n];
+                if query_name(query) == "bogus-id.test" {
+                    let mut bogus = build_response(query, 0, &[(1, vec![192, 0, 2, 1])]);
+                    bogus[1] = bogus[1].wrapping_add(1);
+                    udp.send_to(&bogus, peer).unwrap();
+                }
+                udp.send_to(&answer(query, false), peer).unwrap();
+            }
+        });
+
+        thread_spawn(move || {
+            for mut stream in tcp.incoming().map_while(Result::ok) {
+                let mut len = [0u8; 2];
+                stream.read_exact(&mut len).unwrap();
+                let mut query = vec![0u8; u16::from_be_bytes(len) as usize];
+                stream.read_exact(&mut query).unwrap();
+
+                let response = answer(&query, true);
+                stream.write_all(&(response.len() as u16).to_be_bytes()).unwrap();
+                stream.write_all(&response).unwrap();
+            }
+        });
+
+        addr
+    }
+
+    fn resolver(nameserver: SocketAddr) -> Resolver {
+        let mut config = ResolverConfig { nameservers: vec![nameserver], ..ResolverConfig::default() };
+        config.timeout = Duration::from_millis(500);
+        config.attempts = 1;
+        config.parse_hosts("10.9.9.9 pinned.test\n");
+        Resolver::new(config)
+    }
+
+    #[test]
+    fn parse_resolv_conf_reads_nameservers_and_options() {
+        let mut config = ResolverConfig::default();
+        config.parse_resolv_conf("\
+# generated by test
+nameserver 192.0.2.53
+nameserver 2001:db8::53 ; trailing comment
+nameserver not-an-address
+search example.com
+options ndots:1 timeout:3 attempts:4
+");
+        assert_eq!(config.nameservers, vec![
+            "192.0.2.53:53".parse::<SocketAddr>().unwrap(),
+            "[2001:db8::53]:53".parse::<SocketAddr>().unwrap()
+        ]);
+        assert_eq!(config.timeout, Duration::from_secs(3));
+        assert_eq!(config.attempts, 4);
+
+        config.parse_resolv_conf("options timeout:0 attempts:0\n");
+        assert_eq!(config.timeout, Duration::from_secs(1));
+        assert_eq!(config.attempts, 1);
+    }
+
+    #[test]
+    fn parse_hosts_collects_aliases() {
+        let mut config = ResolverConfig::default();
+        config.parse_hosts("\
+127.0.0.1 localhost Local.Domain # loopback
+::1 localhost
+127.0.0.1 localhost
+garbage line
+");
+        assert_eq!(config.hosts["localhost"], vec![
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            IpAddr::V6(Ipv6Addr::LOCALHOST)
+        ]);
+        assert_eq!(config.hosts["local.domain"], vec![IpAddr::V4(Ipv4Addr::LOCALHOST)]);
+        assert_eq!(config.hosts.len(), 2);
+    }
+
+    #[test]
+    fn resolver_queries_stub_server() {
+        let resolver = resolver(stub_server());
+        block_on(async move {
+            assert_eq!(resolver.lookup_ip("WWW.test.").await.unwrap(), vec![
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+                IpAddr::V6(Ipv6Addr::LOCALHOST)
+            ]);
+            assert_eq!(resolver.lookup_ip("v4only.test").await.unwrap(), vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))]);
+            assert_eq!(resolver.lookup_ip("bogus-id.test").await.unwrap_err().kind(), ErrorKind::NotFound);
+            assert_eq!(resolver.lookup_ip("missing.test").await.unwrap_err().kind(), ErrorKind::NotFound);
+            assert_eq!(
+                resolver.lookup_host("pinned.test", 80).await.unwrap(),
+                vec!["10.9.9.9:80".parse::<SocketAddr>().unwrap()]
+            );
+            assert_eq!(resolver.lookup_ip("192.0.2.7").await.unwrap(), vec![IpAddr::V4(Ipv4Addr::new(192, 0, 2, 7))]);
+        });
+    }
+
+    #[test]
+    fn resolver_retries_truncated_answers_over_tcp() {
+        let resolver = resolver(stub_server());
+        let addrs = block_on(async move { resolver.lookup_ip("big.test").await });
+        assert_eq!(addrs.unwrap(), vec![IpAddr::V4(Ipv4Addr::new(10, 0, 0, 3))]);
+    }
+
+    #[test]
+    fn resolver_times_out_on_silent_server() {
+        let silent = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let resolver = resolver(silent.local_addr().unwrap());
+        let result = block_on(async move { resolver.lookup_ip("www.test").await });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        drop(silent);
+    }
+}
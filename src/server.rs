@@ -0,0 +1,215 @@
+use std::future::Future;
+use std::io::{Error as IOError, Result as IOResult};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::socket::{TcpListener, TcpStream};
+use crate::stream::StreamExt;
+use crate::{Slava, TaskFuture};
+
+struct ConnectionLimit {
+    max_connections: usize,
+    state: Mutex<(usize, Option<Waker>)>
+}
+
+struct ConnectionPermit {
+    limit: Arc<ConnectionLimit>
+}
+
+impl ConnectionLimit {
+    fn acquire(self: &Arc<Self>) -> impl Future<Output = ConnectionPermit> + '_ {
+        struct AcquireFuture<'a> {
+            limit: &'a Arc<ConnectionLimit>
+        }
+
+        impl Future for AcquireFuture<'_> {
+            type Output = ConnectionPermit;
+
+            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                let mut state = self.limit.state.lock().unwrap();
+                if state.0 < self.limit.max_connections {
+                    state.0 += 1;
+                    state.1 = None;
+                    return Poll::Ready(ConnectionPermit { limit: self.limit.clone() });
+                }
+
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+
+        AcquireFuture { limit: self }
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut state = self.limit.state.lock().unwrap();
+        state.0 -= 1;
+        if let Some(waker) = state.1.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub fn serve<H, F>(
+    slava: Arc<Slava>,
+    listener: TcpListener,
+    max_connections: usize,
+    handler: H
+) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send>>
+    where H: Fn(TcpStream, SocketAddr) -> F + Send + Sync + 'static,
+          F: Future<Output=()> + Send + 'static
+{
+    serve_with(listener, max_connections, handler, move |connection| slava.spawn(connection))
+}
+
+pub fn serve_sharded<H, F>(
+    slava: Arc<Slava>,
+    addr: SocketAddr,
+    n_shards: usize,
+    max_connections_per_shard: usize,
+    handler: H
+) -> IOResult<Pin<Box<dyn Future<Output=IOResult<()>> + Send>>>
+    where H: Fn(TcpStream, SocketAddr) -> F + Send + Sync + 'static,
+          F: Future<Output=()> + Send + 'static
+{
+    let mut listeners = Vec::new();
+    for _ in 0..n_shards.max(1) {
+        listeners.push(TcpListener::bind_reuseport(addr)?);
+    }
+
+    Ok(serve_shards(slava, listeners, max_connections_per_shard, handler))
+}
+
+fn serve_shards<H, F>(
+    slava: Arc<Slava>,
+    listeners: Vec<TcpListener>,
+    max_connections_per_shard: usize,
+    handler: H
+) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send>>
+    where H: Fn(TcpStream, SocketAddr) -> F + Send + Sync + 'static,
+          F: Future<Output=()> + Send + 'static
+{
+    let handler = Arc::new(handler);
+    let shards_result = Arc::new(Mutex::new(ShardsResult { error: None, waker: None }));
+    for (worker, listener) in listeners.into_iter().enumerate() {
+        let handler = handler.clone();
+        let shards_result = shards_result.clone();
+        let slava1 = slava.clone();
+
+        slava.spawn_pinned(worker, async move {
+            let shard = serve_with(
+                listener,
+                max_connections_per_shard,
+                move |stream, peer_addr| handler(stream, peer_addr),
+                move |connection| slava1.spawn_pinned(worker, connection)
+            );
+
+            if let Err(e) = shard.await {
+                let mut shards_result = shards_result.lock().unwrap();
+                shards_result.error.get_or_insert(e);
+                if let Some(waker) = shards_result.waker.take() {
+                    waker.wake();
+                }
+            }
+        });
+    }
+
+    Box::pin(ShardsFuture { shards_result })
+}
+
+struct ShardsResult {
+    error: Option<IOError>,
+    waker: Option<Waker>
+}
+
+struct ShardsFuture {
+    shards_result: Arc<Mutex<ShardsResult>>
+}
+
+impl Future for ShardsFuture {
+    type Output = IOResult<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut shards_result = self.shards_result.lock().unwrap();
+        if let Some(e) = shards_result.error.take() {
+            return Poll::Ready(Err(e));
+        }
+
+        shards_result.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+fn serve_with<H, F, S>(
+    mut listener: TcpListener,
+    max_connections: usize,
+    handler: H,
+    spawn: S
+) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send>>
+    where H: Fn(TcpStream, SocketAddr) -> F + Send + Sync + 'static,
+          F: Future<Output=()> + Send + 'static,
+          S: Fn(TaskFuture) + Send + 'static
+{
+    let limit = Arc::new(ConnectionLimit {
+        max_connections: max_connections.max(1),
+        state: Mutex::new((0, None))
+    });
+
+    Box::pin(async move {
+        let mut incoming = listener.incoming();
+        loop {
+            let permit = limit.acquire().await;
+            let Some(accepted) = incoming.next().await else {
+                return Ok(());
+            };
+
+            let (stream, peer_addr) = accepted?;
+            let connection = handler(stream, peer_addr);
+            spawn(Box::pin(async move {
+                connection.await;
+                drop(permit);
+            }));
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+    use std::os::fd::AsRawFd;
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn serve_returns_permanent_accept_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+
+        unsafe { libc::shutdown(listener.as_raw_fd(), libc::SHUT_RDWR) };
+        let result = block_on(serve_with(listener, 1, move |_, peer_addr| {
+            let _ = sender.send(peer_addr);
+            async {}
+        }, drop));
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidInput);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn sharded_server_resolves_with_first_shard_error() {
+        let listeners = (0..2).map(|_| {
+            let listener = TcpListener::bind("127.0.0.1:0".parse().unwrap()).unwrap();
+            unsafe { libc::shutdown(listener.as_raw_fd(), libc::SHUT_RDWR) };
+            listener
+        }).collect::<Vec<_>>();
+
+        let slava = Slava::slava();
+        let server = serve_shards(slava.clone(), listeners, 1, |_, _| async {});
+        std::thread::spawn(move || slava.run(2));
+        assert_eq!(block_on(server).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+}
@@ -1,29 +1,45 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::c_int;
-use std::io::Error as IOError;
+use std::io::{Error as IOError, ErrorKind, IoSlice, IoSliceMut, Result as IOResult};
+use std::net::{Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
 use std::pin::Pin;
 use std::sync::{Mutex, MutexGuard, Once, OnceLock};
 use std::task::{Context, Poll, Waker};
 use std::thread::{sleep as thread_sleep, spawn as spawn_thread};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use crate::sockopt::{self, TcpKeepalive};
+use crate::stream::Stream;
+use crate::time::{self, Sleep};
 
 pub(crate) struct SocketContext {
     pub(crate) readfds: HashMap<c_int, Waker>,
     pub(crate) writefds: HashMap<c_int, Waker>,
 
-    pub(crate) closefds: HashSet<c_int>
+    pub(crate) closefds: HashMap<c_int, Instant>,
+
+    pub(crate) timers: BTreeMap<(Instant, u64), Waker>,
+    pub(crate) next_timer_id: u64
 }
 
 static SOCKET_CONTEXT: OnceLock<Mutex<SocketContext>> = OnceLock::new();
 static SOCKET_BACKGROUND_THREAD: Once = Once::new();
 
+const POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+pub const DEFAULT_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub(crate) fn socket_context_get_or_init<'a>() -> MutexGuard<'a, SocketContext> {
     fn init_socket_context() -> Mutex<SocketContext> {
         Mutex::new(SocketContext {
             readfds: HashMap::new(),
             writefds: HashMap::new(),
 
-            closefds: HashSet::new()
+            closefds: HashMap::new(),
+
+            timers: BTreeMap::new(),
+            next_timer_id: 0
         })
     }
 
@@ -35,20 +51,30 @@ fn maybe_init_background_thread() {
         spawn_thread(|| {
             loop {
                 let mut socket_context = socket_context_get_or_init();
+                let now = Instant::now();
+                while let Some(entry) = socket_context.timers.first_entry() {
+                    if entry.key().0 > now {
+                        break;
+                    }
+                    entry.remove().wake();
+                }
+
                 if !socket_context.closefds.is_empty() {
                     let mut buf = [0u8; 1024];
                     let closefds = socket_context.closefds.clone();
-                    for closefd in closefds {
+                    for (closefd, deadline) in closefds {
                         let bytes_read = unsafe { libc::read(closefd, buf.as_mut_ptr() as *mut _, buf.len()) };
-                        if bytes_read > 0 {
-                            continue;
-                        }
-
-                        if bytes_read < 0 {
-                            let errno = unsafe { *libc::__errno_location() };
-                            if errno == libc::EAGAIN && errno == libc::EWOULDBLOCK {
+                        if deadline > now {
+                            if bytes_read > 0 {
                                 continue;
                             }
+
+                            if bytes_read < 0 {
+                                let errno = unsafe { *libc::__errno_location() };
+                                if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
+                                    continue;
+                                }
+                            }
                         }
 
                         unsafe { libc::close(closefd) };
@@ -56,10 +82,14 @@ fn maybe_init_background_thread() {
                     }
                 }
 
+                let poll_timeout = socket_context.timers.first_key_value()
+                    .map(|((deadline, _), _)| deadline.saturating_duration_since(now).min(POLL_INTERVAL))
+                    .unwrap_or(POLL_INTERVAL);
+
                 let nfds = socket_context.readfds.len() + socket_context.writefds.len();
                 if nfds == 0 {
                     drop(socket_context);
-                    thread_sleep(Duration::from_millis(16));
+                    thread_sleep(poll_timeout);
                     continue;
                 }
 
@@ -80,10 +110,15 @@ fn maybe_init_background_thread() {
                 }
                 drop(socket_context);
 
-                let fd = unsafe { libc::poll(poll_fds.as_mut_ptr(), nfds as libc::nfds_t, 16) };
+                let timeout_ms = poll_timeout.as_micros().div_ceil(1000) as c_int;
+                let fd = unsafe { libc::poll(poll_fds.as_mut_ptr(), nfds as libc::nfds_t, timeout_ms) };
 
                 if fd < 0 {
-                    panic!("poll failed, error code = {}", unsafe { *libc::__errno_location() });
+                    let errno = unsafe { *libc::__errno_location() };
+                    if errno == libc::EINTR {
+                        continue;
+                    }
+                    panic!("poll failed, error code = {}", errno);
                 }
 
                 if fd == 0 {
@@ -107,6 +142,95 @@ fn maybe_init_background_thread() {
     });
 }
 
+pub(crate) fn check_os_error(result: c_int) -> IOResult<c_int> {
+    if result < 0 {
+        Err(IOError::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+pub(crate) fn new_socket(domain: c_int, ty: c_int) -> IOResult<c_int> {
+    check_os_error(unsafe { libc::socket(domain, ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, 0) })
+}
+
+pub(crate) fn set_nonblocking(fd: c_int) -> IOResult<()> {
+    let flags = check_os_error(unsafe { libc::fcntl(fd, libc::F_GETFL) })?;
+    check_os_error(unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) })?;
+    Ok(())
+}
+
+pub(crate) fn check_os_error_size(result: isize) -> IOResult<usize> {
+    if result < 0 {
+        Err(IOError::last_os_error())
+    } else {
+        Ok(result as usize)
+    }
+}
+
+pub(crate) fn sockaddr_to_socket_addr(
+    storage: &libc::sockaddr_storage,
+    len: libc::socklen_t
+) -> IOResult<SocketAddr> {
+    match storage.ss_family as c_int {
+        libc::AF_INET if len as usize >= std::mem::size_of::<libc::sockaddr_in>() => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in) };
+            Ok(SocketAddr::V4(SocketAddrV4::new(
+                Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)),
+                u16::from_be(addr.sin_port)
+            )))
+        },
+        libc::AF_INET6 if len as usize >= std::mem::size_of::<libc::sockaddr_in6>() => {
+            let addr = unsafe { &*(storage as *const _ as *const libc::sockaddr_in6) };
+            Ok(SocketAddr::V6(SocketAddrV6::new(
+                Ipv6Addr::from(addr.sin6_addr.s6_addr),
+                u16::from_be(addr.sin6_port),
+                addr.sin6_flowinfo,
+                addr.sin6_scope_id
+            )))
+        },
+        _ => Err(IOError::new(ErrorKind::InvalidInput, "unsupported address family"))
+    }
+}
+
+pub(crate) fn socket_addr_to_sockaddr(addr: &SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let len = match addr {
+        SocketAddr::V4(addr) => {
+            let sockaddr = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in) };
+            sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+            sockaddr.sin_port = addr.port().to_be();
+            sockaddr.sin_addr = libc::in_addr { s_addr: u32::from(*addr.ip()).to_be() };
+            std::mem::size_of::<libc::sockaddr_in>()
+        },
+        SocketAddr::V6(addr) => {
+            let sockaddr = unsafe { &mut *(&mut storage as *mut _ as *mut libc::sockaddr_in6) };
+            sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sockaddr.sin6_port = addr.port().to_be();
+            sockaddr.sin6_addr = libc::in6_addr { s6_addr: addr.ip().octets() };
+            sockaddr.sin6_flowinfo = addr.flowinfo();
+            sockaddr.sin6_scope_id = addr.scope_id();
+            std::mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (storage, len as libc::socklen_t)
+}
+
+pub(crate) fn getsockname(fd: c_int) -> IOResult<SocketAddr> {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&storage) as libc::socklen_t;
+    check_os_error(unsafe { libc::getsockname(fd, &mut storage as *mut _ as *mut _, &mut len) })?;
+    sockaddr_to_socket_addr(&storage, len)
+}
+
+pub(crate) fn getpeername(fd: c_int) -> IOResult<SocketAddr> {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&storage) as libc::socklen_t;
+    check_os_error(unsafe { libc::getpeername(fd, &mut storage as *mut _ as *mut _, &mut len) })?;
+    sockaddr_to_socket_addr(&storage, len)
+}
+
 pub(crate) fn add_read_fd(fd: c_int, waker: Waker) {
     maybe_init_background_thread();
     socket_context_get_or_init().readfds.insert(fd, waker);
@@ -117,76 +241,454 @@ pub(crate) fn add_write_fd(fd: c_int, waker: Waker) {
     socket_context_get_or_init().writefds.insert(fd, waker);
 }
 
-#[derive(Debug)]
-pub struct TcpListener {
-    sockfd: c_int
+pub(crate) fn add_timer(deadline: Instant, waker: Waker) -> (Instant, u64) {
+    maybe_init_background_thread();
+    let mut socket_context = socket_context_get_or_init();
+    let key = (deadline, socket_context.next_timer_id);
+    socket_context.next_timer_id += 1;
+    socket_context.timers.insert(key, waker);
+    key
 }
 
-impl TcpListener {
-    pub fn new(port: u16) -> Self {
-        let sockfd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_STREAM, 0) };
-        unsafe {
-            libc::fcntl(sockfd, libc::F_SETFL, libc::O_NONBLOCK);
-            libc::setsockopt(
-                sockfd,
-                libc::SOL_SOCKET,
-                libc::SO_REUSEADDR,
-                &1 as *const _ as *const _,
-                std::mem::size_of::<c_int>() as _
-            );
+pub(crate) fn update_timer(key: (Instant, u64), waker: Waker) {
+    socket_context_get_or_init().timers.insert(key, waker);
+}
+
+pub(crate) fn remove_timer(key: (Instant, u64)) {
+    socket_context_get_or_init().timers.remove(&key);
+}
+
+pub(crate) struct IoFuture<F> {
+    fd: c_int,
+    write: bool,
+    op: F
+}
+
+impl<F> IoFuture<F> {
+    pub(crate) fn read(fd: c_int, op: F) -> Self {
+        Self { fd, write: false, op }
+    }
+
+    pub(crate) fn write(fd: c_int, op: F) -> Self {
+        Self { fd, write: true, op }
+    }
+}
+
+impl<T, F: FnMut() -> IOResult<T> + Unpin> Future for IoFuture<F> {
+    type Output = IOResult<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match (self.op)() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                let waker = cx.waker().clone();
+                if self.write {
+                    add_write_fd(self.fd, waker);
+                } else {
+                    add_read_fd(self.fd, waker);
+                }
+                Poll::Pending
+            },
+            result => Poll::Ready(result)
         }
+    }
+}
 
-        let server_addr = libc::sockaddr_in {
-            sin_family: libc::AF_INET as u16,
-            sin_port: port.to_be(),
-            sin_addr: libc::in_addr { s_addr: libc::INADDR_ANY },
-            sin_zero: [0; 8],
-        };
+pub(crate) fn read_fd_bytes<'a>(
+    fd: c_int,
+    buf: &'a mut [u8]
+) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+    struct ReadFuture<'b> {
+        fd: c_int,
+        buf: &'b mut [u8]
+    }
+
+    impl<'b> Future for ReadFuture<'b> {
+        type Output = IOResult<usize>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            unsafe {
+                let bytes_read = libc::read(
+                    self.fd,
+                    self.buf.as_mut_ptr() as *mut _,
+                    self.buf.len()
+                );
 
-        unsafe {
-            libc::bind(
-                sockfd,
-                &server_addr as *const _ as *const libc::sockaddr,
-                std::mem::size_of_val(&server_addr) as u32,
-            );
-            let listen_result = libc::listen(sockfd, 5);
-            if listen_result != 0 {
-                panic!("listen failed, error code = {listen_result}");
+                if bytes_read > 0 {
+                    Poll::Ready(Ok(bytes_read as usize))
+                } else if bytes_read == 0 {
+                    Poll::Ready(Ok(0))
+                } else {
+                    let errno = *libc::__errno_location();
+                    if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                    }
+
+                    let waker = cx.waker().clone();
+                    add_read_fd(self.fd, waker);
+                    Poll::Pending
+                }
             }
         }
+    }
+
+    Box::pin(ReadFuture { fd, buf })
+}
 
-        Self { sockfd }
+pub(crate) fn write_fd_bytes<'a>(
+    fd: c_int,
+    buf: &'a [u8]
+) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+    struct WriteFuture<'b> {
+        fd: c_int,
+        buf: &'b [u8],
+        bytes_written: usize
     }
 
-    pub fn accept(&mut self) -> Pin<Box<dyn Future<Output=Result<TcpStream, String>> + Send + Sync>> {
-        struct AcceptFuture {
-            listener_sockfd: c_int
-        }
+    impl<'b> Future for WriteFuture<'b> {
+        type Output = IOResult<usize>;
 
-        impl Future for AcceptFuture {
-            type Output = Result<TcpStream, String>;
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            unsafe {
+                let slice_to_write = &self.buf[self.bytes_written..];
+                let bytes_written = libc::write(
+                    self.fd,
+                    slice_to_write.as_ptr() as *const _,
+                    slice_to_write.len()
+                );
 
-            fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                unsafe {
-                    let fd = libc::accept(self.listener_sockfd, std::ptr::null_mut(), std::ptr::null_mut());
-                    if fd >= 0 {
-                        libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK);
-                        return Poll::Ready(Ok(TcpStream { fd }));
+                if bytes_written == 0 && !slice_to_write.is_empty() {
+                    return Poll::Ready(Err(IOError::from(ErrorKind::WriteZero)));
+                }
+
+                if bytes_written >= 0 {
+                    self.bytes_written += bytes_written as usize;
+                    if self.bytes_written == self.buf.len() {
+                        return Poll::Ready(Ok(self.bytes_written));
                     }
 
+                    let waker = cx.waker().clone();
+                    add_write_fd(self.fd, waker);
+                    Poll::Pending
+                } else {
                     let errno = *libc::__errno_location();
-                    if errno != libc::EAGAIN {
-                        return Poll::Ready(Err(format!("accept failed: {}", IOError::from_raw_os_error(errno))));
+                    if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
                     }
 
                     let waker = cx.waker().clone();
-                    add_read_fd(self.listener_sockfd, waker);
+                    add_write_fd(self.fd, waker);
                     Poll::Pending
                 }
             }
         }
+    }
+
+    Box::pin(WriteFuture { fd, buf, bytes_written: 0 })
+}
+
+fn iovec_count(n_bufs: usize) -> c_int {
+    n_bufs.min(libc::UIO_MAXIOV as usize) as c_int
+}
+
+pub(crate) fn read_vectored_fd<'a>(
+    fd: c_int,
+    bufs: &'a mut [IoSliceMut<'_>]
+) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+    Box::pin(IoFuture::read(fd, move || check_os_error_size(unsafe {
+        libc::readv(fd, bufs.as_ptr() as *const libc::iovec, iovec_count(bufs.len()))
+    })))
+}
+
+pub(crate) fn write_vectored_fd<'a>(
+    fd: c_int,
+    bufs: &'a [IoSlice<'_>]
+) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+    Box::pin(IoFuture::write(fd, move || check_os_error_size(unsafe {
+        libc::writev(fd, bufs.as_ptr() as *const libc::iovec, iovec_count(bufs.len()))
+    })))
+}
+
+pub(crate) fn write_all_vectored_fd<'a, 'b: 'a>(
+    fd: c_int,
+    bufs: &'a mut [IoSlice<'b>]
+) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+    struct WriteAllVectoredFuture<'c, 'd> {
+        fd: c_int,
+        bufs: &'c mut [IoSlice<'d>],
+        bytes_written: usize
+    }
+
+    impl<'c, 'd> Future for WriteAllVectoredFuture<'c, 'd> {
+        type Output = IOResult<usize>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            loop {
+                let mut bufs = std::mem::take(&mut self.bufs);
+                IoSlice::advance_slices(&mut bufs, 0);
+                self.bufs = bufs;
+                if self.bufs.is_empty() {
+                    return Poll::Ready(Ok(self.bytes_written));
+                }
+
+                let bytes_written = unsafe {
+                    libc::writev(
+                        self.fd,
+                        self.bufs.as_ptr() as *const libc::iovec,
+                        iovec_count(self.bufs.len())
+                    )
+                };
+
+                if bytes_written == 0 {
+                    return Poll::Ready(Err(IOError::from(ErrorKind::WriteZero)));
+                }
+
+                if bytes_written < 0 {
+                    let errno = unsafe { *libc::__errno_location() };
+                    if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                    }
+
+                    let waker = cx.waker().clone();
+                    add_write_fd(self.fd, waker);
+                    return Poll::Pending;
+                }
+
+                self.bytes_written += bytes_written as usize;
+                let mut bufs = std::mem::take(&mut self.bufs);
+                IoSlice::advance_slices(&mut bufs, bytes_written as usize);
+                self.bufs = bufs;
+            }
+        }
+    }
+
+    Box::pin(WriteAllVectoredFuture { fd, bufs, bytes_written: 0 })
+}
+
+pub(crate) fn shutdown_fd(fd: c_int, how: Shutdown) -> IOResult<()> {
+    let how = match how {
+        Shutdown::Read => libc::SHUT_RD,
+        Shutdown::Write => libc::SHUT_WR,
+        Shutdown::Both => libc::SHUT_RDWR
+    };
+    check_os_error(unsafe { libc::shutdown(fd, how) })?;
+    Ok(())
+}
+
+pub(crate) fn poll_read_fd(fd: c_int, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<IOResult<usize>> {
+    let bytes_read = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut _, buf.len() as _) };
+    if bytes_read < 0 {
+        let errno = unsafe { *libc::__errno_location() };
+        if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
+            let waker = cx.waker().clone();
+            add_read_fd(fd, waker);
+            return Poll::Pending;
+        }
 
-        Box::pin(AcceptFuture { listener_sockfd: self.sockfd })
+        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+    }
+
+    Poll::Ready(Ok(bytes_read as usize))
+}
+
+pub(crate) fn poll_write_fd(fd: c_int, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+    if buf.is_empty() {
+        return Poll::Ready(Ok(0));
+    }
+
+    let bytes_written = unsafe { libc::write(fd, buf.as_ptr() as *const _, buf.len() as _) };
+    if bytes_written < 0 {
+        let errno = unsafe { *libc::__errno_location() };
+        if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
+            let waker = cx.waker().clone();
+            add_write_fd(fd, waker);
+            return Poll::Pending;
+        }
+
+        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+    }
+
+    if bytes_written == 0 {
+        let waker = cx.waker().clone();
+        add_write_fd(fd, waker);
+        return Poll::Pending;
+    }
+
+    Poll::Ready(Ok(bytes_written as usize))
+}
+
+pub(crate) fn poll_write_vectored_fd(fd: c_int, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<IOResult<usize>> {
+    let bytes_written = unsafe {
+        libc::writev(fd, bufs.as_ptr() as *const libc::iovec, iovec_count(bufs.len()))
+    };
+    if bytes_written < 0 {
+        let errno = unsafe { *libc::__errno_location() };
+        if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
+            let waker = cx.waker().clone();
+            add_write_fd(fd, waker);
+            return Poll::Pending;
+        }
+
+        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+    }
+
+    Poll::Ready(Ok(bytes_written as usize))
+}
+
+pub(crate) fn poll_shutdown_fd(fd: c_int) -> Poll<IOResult<()>> {
+    socket_context_get_or_init().writefds.remove(&fd);
+    Poll::Ready(shutdown_fd(fd, Shutdown::Write))
+}
+
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+pub(crate) struct RawAcceptFuture {
+    listener_fd: c_int,
+    backoff: Option<Sleep>
+}
+
+impl RawAcceptFuture {
+    pub(crate) fn new(listener_fd: c_int) -> Self {
+        Self { listener_fd, backoff: None }
+    }
+}
+
+impl Future for RawAcceptFuture {
+    type Output = IOResult<(c_int, libc::sockaddr_storage, libc::socklen_t)>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(backoff) = self.backoff.as_mut() {
+            if Pin::new(backoff).poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            self.backoff = None;
+        }
+
+        loop {
+            let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of_val(&storage) as libc::socklen_t;
+            let fd = unsafe {
+                libc::accept4(
+                    self.listener_fd,
+                    &mut storage as *mut _ as *mut _,
+                    &mut len,
+                    libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC
+                )
+            };
+            if fd >= 0 {
+                return Poll::Ready(Ok((fd, storage, len)));
+            }
+
+            let errno = unsafe { *libc::__errno_location() };
+            match errno {
+                libc::EINTR | libc::ECONNABORTED | libc::EPROTO => continue,
+                libc::EAGAIN => {
+                    let waker = cx.waker().clone();
+                    add_read_fd(self.listener_fd, waker);
+                    return Poll::Pending;
+                },
+                libc::EMFILE | libc::ENFILE | libc::ENOBUFS | libc::ENOMEM => {
+                    let mut backoff = time::sleep(ACCEPT_BACKOFF);
+                    let _ = Pin::new(&mut backoff).poll(cx);
+                    self.backoff = Some(backoff);
+                    return Poll::Pending;
+                },
+                _ => return Poll::Ready(Err(IOError::from_raw_os_error(errno)))
+            }
+        }
+    }
+}
+
+pub(crate) fn remove_fd(fd: c_int) {
+    let mut socket_context = socket_context_get_or_init();
+    socket_context.readfds.remove(&fd);
+    socket_context.writefds.remove(&fd);
+}
+
+pub const DEFAULT_BACKLOG: u32 = 1024;
+
+#[derive(Debug)]
+pub struct TcpListener {
+    sockfd: c_int
+}
+
+impl TcpListener {
+    pub fn new(port: u16) -> IOResult<Self> {
+        Self::bind(SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, port)))
+    }
+
+    pub fn bind(addr: SocketAddr) -> IOResult<Self> {
+        let socket = TcpSocket::for_addr(&addr)?;
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        socket.listen(DEFAULT_BACKLOG)
+    }
+
+    pub fn bind_reuseport(addr: SocketAddr) -> IOResult<Self> {
+        let socket = TcpSocket::for_addr(&addr)?;
+        socket.set_reuseaddr(true)?;
+        socket.set_reuseport(true)?;
+        socket.bind(addr)?;
+        socket.listen(DEFAULT_BACKLOG)
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> IOResult<()> {
+        sockopt::set_ttl(self.sockfd, ttl)
+    }
+
+    pub fn ttl(&self) -> IOResult<u32> {
+        sockopt::ttl(self.sockfd)
+    }
+
+    pub fn reuseport(&self) -> IOResult<bool> {
+        sockopt::reuseport(self.sockfd)
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.sockfd)
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        getsockname(self.sockfd)
+    }
+
+    pub fn incoming(&mut self) -> Incoming<'_> {
+        Incoming { listener: self, accept: None }
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn accept(&mut self) -> Pin<Box<dyn Future<Output=IOResult<(TcpStream, SocketAddr)>> + Send + Sync>> {
+        let accept = RawAcceptFuture::new(self.sockfd);
+        Box::pin(async move {
+            let (fd, storage, len) = accept.await?;
+            let stream = TcpStream { fd };
+            Ok((stream, sockaddr_to_socket_addr(&storage, len)?))
+        })
+    }
+}
+
+pub struct Incoming<'a> {
+    listener: &'a mut TcpListener,
+    accept: Option<RawAcceptFuture>
+}
+
+impl Stream for Incoming<'_> {
+    type Item = IOResult<(TcpStream, SocketAddr)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let listener_fd = self.listener.sockfd;
+        let accept = self.accept.get_or_insert_with(|| RawAcceptFuture::new(listener_fd));
+        let (fd, storage, len) = match Pin::new(accept).poll(cx) {
+            Poll::Ready(Ok(accepted)) => accepted,
+            Poll::Ready(Err(e)) => {
+                self.accept = None;
+                return Poll::Ready(Some(Err(e)));
+            },
+            Poll::Pending => return Poll::Pending
+        };
+
+        self.accept = None;
+        let stream = TcpStream { fd };
+        Poll::Ready(Some(sockaddr_to_socket_addr(&storage, len).map(|addr| (stream, addr))))
     }
 }
 
@@ -202,103 +704,399 @@ pub struct TcpStream {
 }
 
 impl TcpStream {
+    pub fn connect(addr: SocketAddr) -> Pin<Box<dyn Future<Output=IOResult<TcpStream>> + Send + Sync>> {
+        match TcpSocket::for_addr(&addr) {
+            Ok(socket) => socket.connect(addr),
+            Err(e) => Box::pin(std::future::ready(Err(e)))
+        }
+    }
+
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        getpeername(self.fd)
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        getsockname(self.fd)
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> IOResult<()> {
+        sockopt::set_nodelay(self.fd, nodelay)
+    }
+
+    pub fn nodelay(&self) -> IOResult<bool> {
+        sockopt::nodelay(self.fd)
+    }
+
+    pub fn set_cork(&self, cork: bool) -> IOResult<()> {
+        sockopt::set_cork(self.fd, cork)
+    }
+
+    pub fn cork(&self) -> IOResult<bool> {
+        sockopt::cork(self.fd)
+    }
+
+    pub fn set_keepalive(&self, keepalive: Option<TcpKeepalive>) -> IOResult<()> {
+        sockopt::set_keepalive(self.fd, keepalive)
+    }
+
+    pub fn keepalive(&self) -> IOResult<Option<TcpKeepalive>> {
+        sockopt::keepalive(self.fd)
+    }
+
+    pub fn set_linger(&self, linger: Option<Duration>) -> IOResult<()> {
+        sockopt::set_linger(self.fd, linger)
+    }
+
+    pub fn linger(&self) -> IOResult<Option<Duration>> {
+        sockopt::linger(self.fd)
+    }
+
+    pub fn set_recv_buffer_size(&self, size: u32) -> IOResult<()> {
+        sockopt::set_recv_buffer_size(self.fd, size)
+    }
+
+    pub fn recv_buffer_size(&self) -> IOResult<u32> {
+        sockopt::recv_buffer_size(self.fd)
+    }
+
+    pub fn set_send_buffer_size(&self, size: u32) -> IOResult<()> {
+        sockopt::set_send_buffer_size(self.fd, size)
+    }
+
+    pub fn send_buffer_size(&self) -> IOResult<u32> {
+        sockopt::send_buffer_size(self.fd)
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> IOResult<()> {
+        sockopt::set_ttl(self.fd, ttl)
+    }
+
+    pub fn ttl(&self) -> IOResult<u32> {
+        sockopt::ttl(self.fd)
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.fd)
+    }
+
     pub fn read_bytes<'a>(
         &self,
         buf: &'a mut [u8]
-    ) -> Pin<Box<dyn 'a + Future<Output=Result<usize, String>> + Send + Sync>> {
-        struct ReadFuture<'b> {
-            fd: c_int,
-            buf: &'b mut [u8]
-        }
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        read_fd_bytes(self.fd, buf)
+    }
 
-        impl<'b> Future for ReadFuture<'b> {
-            type Output = Result<usize, String>;
+    pub fn write_bytes<'a>(
+        &mut self,
+        buf: &'a [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_fd_bytes(self.fd, buf)
+    }
 
-            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                unsafe {
-                    let bytes_read = libc::read(
-                        self.fd,
-                        self.buf.as_mut_ptr() as *mut _,
-                        self.buf.len()
-                    );
-
-                    if bytes_read > 0 {
-                        Poll::Ready(Ok(bytes_read as usize))
-                    } else if bytes_read == 0 {
-                        Poll::Ready(Ok(0))
-                    } else {
-                        let errno = *libc::__errno_location();
-                        if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
-                            return Poll::Ready(Err(format!("read_bytes failed: {}", IOError::from_raw_os_error(errno))));
-                        }
+    pub fn shutdown(&self, how: Shutdown) -> IOResult<()> {
+        shutdown_fd(self.fd, how)
+    }
 
-                        let waker = cx.waker().clone();
-                        add_read_fd(self.fd, waker);
-                        Poll::Pending
-                    }
+    pub fn close(self) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send + Sync>> {
+        self.close_timeout(DEFAULT_CLOSE_TIMEOUT)
+    }
+
+    pub fn close_timeout(self, timeout: Duration) -> Pin<Box<dyn Future<Output=IOResult<()>> + Send + Sync>> {
+        Box::pin(async move {
+            let fd = self.fd;
+            let result = async {
+                match shutdown_fd(fd, Shutdown::Write) {
+                    Err(e) if e.kind() == ErrorKind::NotConnected => return Ok(()),
+                    result => result?
                 }
-            }
-        }
 
-        Box::pin(ReadFuture { fd: self.fd, buf })
+                let mut buf = [0u8; 1024];
+                time::timeout(timeout, async {
+                    while read_fd_bytes(fd, &mut buf).await? != 0 {}
+                    Ok(())
+                }).await?
+            }.await;
+
+            let fd = self.into_raw_fd();
+            check_os_error(unsafe { libc::close(fd) })?;
+            result
+        })
     }
 
-    pub fn write_bytes<'a>(
+    pub fn read_vectored<'a>(
+        &self,
+        bufs: &'a mut [IoSliceMut<'_>]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        read_vectored_fd(self.fd, bufs)
+    }
+
+    pub fn write_vectored<'a>(
         &mut self,
-        buf: &'a [u8]
-    ) -> Pin<Box<dyn 'a + Future<Output=Result<usize, String>> + Send + Sync>> {
-        struct WriteFuture<'b> {
-            fd: c_int,
-            buf: &'b [u8],
-            bytes_written: usize
+        bufs: &'a [IoSlice<'_>]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_vectored_fd(self.fd, bufs)
+    }
+
+    pub fn write_all_vectored<'a, 'b: 'a>(
+        &mut self,
+        bufs: &'a mut [IoSlice<'b>]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_all_vectored_fd(self.fd, bufs)
+    }
+}
+
+impl Drop for TcpStream {
+    fn drop(&mut self) {
+        remove_fd(self.fd);
+
+        if unsafe { libc::shutdown(self.fd, libc::SHUT_WR) } != 0 {
+            unsafe { libc::close(self.fd) };
+            return;
         }
 
-        impl<'b> Future for WriteFuture<'b> {
-            type Output = Result<usize, String>;
+        socket_context_get_or_init().closefds.insert(self.fd, Instant::now() + DEFAULT_CLOSE_TIMEOUT);
+    }
+}
+
+impl AsRawFd for TcpListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.sockfd
+    }
+}
+
+impl AsRawFd for TcpStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl FromRawFd for TcpStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let _ = set_nonblocking(fd);
+        Self { fd }
+    }
+}
+
+impl IntoRawFd for TcpStream {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        remove_fd(fd);
+        std::mem::forget(self);
+        fd
+    }
+}
+
+#[derive(Debug)]
+pub struct TcpSocket {
+    fd: c_int
+}
+
+impl TcpSocket {
+    pub fn new_v4() -> IOResult<Self> {
+        Self::new(libc::AF_INET)
+    }
+
+    pub fn new_v6() -> IOResult<Self> {
+        Self::new(libc::AF_INET6)
+    }
+
+    fn new(domain: c_int) -> IOResult<Self> {
+        Ok(Self { fd: new_socket(domain, libc::SOCK_STREAM)? })
+    }
+
+    pub(crate) fn for_addr(addr: &SocketAddr) -> IOResult<Self> {
+        match addr {
+            SocketAddr::V4(_) => Self::new_v4(),
+            SocketAddr::V6(_) => Self::new_v6()
+        }
+    }
+
+    pub fn set_reuseaddr(&self, reuseaddr: bool) -> IOResult<()> {
+        sockopt::set_reuseaddr(self.fd, reuseaddr)
+    }
+
+    pub fn reuseaddr(&self) -> IOResult<bool> {
+        sockopt::reuseaddr(self.fd)
+    }
+
+    pub fn set_reuseport(&self, reuseport: bool) -> IOResult<()> {
+        sockopt::set_reuseport(self.fd, reuseport)
+    }
+
+    pub fn reuseport(&self) -> IOResult<bool> {
+        sockopt::reuseport(self.fd)
+    }
+
+    pub fn set_nodelay(&self, nodelay: bool) -> IOResult<()> {
+        sockopt::set_nodelay(self.fd, nodelay)
+    }
+
+    pub fn nodelay(&self) -> IOResult<bool> {
+        sockopt::nodelay(self.fd)
+    }
+
+    pub fn set_keepalive(&self, keepalive: Option<TcpKeepalive>) -> IOResult<()> {
+        sockopt::set_keepalive(self.fd, keepalive)
+    }
+
+    pub fn keepalive(&self) -> IOResult<Option<TcpKeepalive>> {
+        sockopt::keepalive(self.fd)
+    }
+
+    pub fn set_linger(&self, linger: Option<Duration>) -> IOResult<()> {
+        sockopt::set_linger(self.fd, linger)
+    }
+
+    pub fn linger(&self) -> IOResult<Option<Duration>> {
+        sockopt::linger(self.fd)
+    }
+
+    pub fn set_recv_buffer_size(&self, size: u32) -> IOResult<()> {
+        sockopt::set_recv_buffer_size(self.fd, size)
+    }
+
+    pub fn recv_buffer_size(&self) -> IOResult<u32> {
+        sockopt::recv_buffer_size(self.fd)
+    }
+
+    pub fn set_send_buffer_size(&self, size: u32) -> IOResult<()> {
+        sockopt::set_send_buffer_size(self.fd, size)
+    }
+
+    pub fn send_buffer_size(&self) -> IOResult<u32> {
+        sockopt::send_buffer_size(self.fd)
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> IOResult<()> {
+        sockopt::set_ttl(self.fd, ttl)
+    }
+
+    pub fn ttl(&self) -> IOResult<u32> {
+        sockopt::ttl(self.fd)
+    }
+
+    pub fn bind(&self, addr: SocketAddr) -> IOResult<()> {
+        let (storage, len) = socket_addr_to_sockaddr(&addr);
+        check_os_error(unsafe { libc::bind(self.fd, &storage as *const _ as *const _, len) })?;
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        getsockname(self.fd)
+    }
+
+    pub fn listen(self, backlog: u32) -> IOResult<TcpListener> {
+        let backlog = backlog.min(c_int::MAX as u32) as c_int;
+        check_os_error(unsafe { libc::listen(self.fd, backlog) })?;
+        Ok(TcpListener { sockfd: self.into_raw_fd() })
+    }
+
+    pub fn connect(self, addr: SocketAddr) -> Pin<Box<dyn Future<Output=IOResult<TcpStream>> + Send + Sync>> {
+        struct ConnectFuture {
+            socket: Option<TcpSocket>,
+            addr: SocketAddr,
+            connecting: bool
+        }
+
+        impl Future for ConnectFuture {
+            type Output = IOResult<TcpStream>;
 
             fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-                unsafe {
-                    let slice_to_write = &self.buf[self.bytes_written..];
-                    let bytes_written = libc::write(
-                        self.fd,
-                        slice_to_write.as_ptr() as *const _,
-                        slice_to_write.len()
-                    );
-
-                    if bytes_written >= 0 {
-                        self.bytes_written += bytes_written as usize;
-                        if self.bytes_written == self.buf.len() {
-                            return Poll::Ready(Ok(self.bytes_written));
+                let Some(fd) = self.socket.as_ref().map(|socket| socket.fd) else {
+                    panic!("ConnectFuture polled after completion");
+                };
+
+                if !self.connecting {
+                    let (storage, len) = socket_addr_to_sockaddr(&self.addr);
+                    let result = unsafe { libc::connect(fd, &storage as *const _ as *const _, len) };
+                    if result < 0 {
+                        let errno = unsafe { *libc::__errno_location() };
+                        if errno != libc::EINPROGRESS {
+                            self.socket = None;
+                            return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                        }
+
+                        self.connecting = true;
+                        add_write_fd(fd, cx.waker().clone());
+                        return Poll::Pending;
+                    }
+                } else {
+                    match sockopt::take_error(fd) {
+                        Ok(None) => {},
+                        Ok(Some(e)) | Err(e) => {
+                            self.socket = None;
+                            return Poll::Ready(Err(e));
                         }
+                    }
 
-                        let waker = cx.waker().clone();
-                        add_write_fd(self.fd, waker);
-                        Poll::Pending
-                    } else {
-                        let errno = *libc::__errno_location();
-                        if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
-                            return Poll::Ready(Err(format!("write_bytes failed: {}", IOError::from_raw_os_error(errno))));
+                    if let Err(e) = getpeername(fd) {
+                        if e.raw_os_error() == Some(libc::ENOTCONN) {
+                            add_write_fd(fd, cx.waker().clone());
+                            return Poll::Pending;
                         }
 
-                        let waker = cx.waker().clone();
-                        add_write_fd(self.fd, waker);
-                        Poll::Pending
+                        self.socket = None;
+                        return Poll::Ready(Err(e));
                     }
                 }
+
+                let socket = self.socket.take().unwrap();
+                Poll::Ready(Ok(TcpStream { fd: socket.into_raw_fd() }))
             }
         }
 
-        Box::pin(WriteFuture { fd: self.fd, buf, bytes_written: 0 })
+        Box::pin(ConnectFuture { socket: Some(self), addr, connecting: false })
+    }
+
+    fn into_raw_fd(self) -> c_int {
+        let fd = self.fd;
+        std::mem::forget(self);
+        fd
     }
 }
 
-impl Drop for TcpStream {
+impl AsRawFd for TcpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for TcpSocket {
     fn drop(&mut self) {
-        unsafe { libc::shutdown(self.fd, libc::SHUT_WR) };
+        remove_fd(self.fd);
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener as StdTcpListener;
+    use std::thread::sleep;
 
-        let mut socket_context = socket_context_get_or_init();
-        socket_context.closefds.insert(self.fd);
-        socket_context.readfds.remove(&self.fd);
-        socket_context.writefds.remove(&self.fd);
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn dropping_close_future_mid_linger_releases_fd() {
+        let listener = StdTcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let stream = block_on(TcpStream::connect(addr)).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+
+        let fd = stream.as_raw_fd();
+        let (result, parked) = block_on(async move {
+            let result = time::timeout(Duration::from_millis(50), stream.close_timeout(Duration::from_secs(30))).await;
+            let socket_context = socket_context_get_or_init();
+            (result, socket_context.closefds.contains_key(&fd) && !socket_context.readfds.contains_key(&fd))
+        });
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::TimedOut);
+        assert!(parked);
+
+        drop(peer);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while socket_context_get_or_init().closefds.contains_key(&fd) {
+            assert!(Instant::now() < deadline, "fd was never closed");
+            sleep(Duration::from_millis(10));
+        }
     }
 }
@@ -0,0 +1,149 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::io::Result as IOResult;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use crate::socket::{read_fd_bytes, write_fd_bytes, TcpStream};
+
+#[derive(Debug)]
+pub struct ReadHalf<'a> {
+    pub(crate) stream: &'a TcpStream
+}
+
+#[derive(Debug)]
+pub struct WriteHalf<'a> {
+    pub(crate) stream: &'a TcpStream
+}
+
+#[derive(Debug)]
+pub struct OwnedReadHalf {
+    pub(crate) stream: Arc<TcpStream>
+}
+
+#[derive(Debug)]
+pub struct OwnedWriteHalf {
+    pub(crate) stream: Arc<TcpStream>,
+    shutdown_on_drop: bool
+}
+
+#[derive(Debug)]
+pub struct ReuniteError(pub OwnedReadHalf, pub OwnedWriteHalf);
+
+impl TcpStream {
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        (ReadHalf { stream: self }, WriteHalf { stream: self })
+    }
+
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        let stream = Arc::new(self);
+        (
+            OwnedReadHalf { stream: stream.clone() },
+            OwnedWriteHalf { stream, shutdown_on_drop: true }
+        )
+    }
+}
+
+impl ReadHalf<'_> {
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn read_bytes<'b>(
+        &self,
+        buf: &'b mut [u8]
+    ) -> Pin<Box<dyn 'b + Future<Output=IOResult<usize>> + Send + Sync>> {
+        read_fd_bytes(self.stream.fd, buf)
+    }
+}
+
+impl WriteHalf<'_> {
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn write_bytes<'b>(
+        &mut self,
+        buf: &'b [u8]
+    ) -> Pin<Box<dyn 'b + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_fd_bytes(self.stream.fd, buf)
+    }
+}
+
+impl OwnedReadHalf {
+    pub fn reunite(self, other: OwnedWriteHalf) -> Result<TcpStream, ReuniteError> {
+        reunite(self, other)
+    }
+
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn read_bytes<'a>(
+        &self,
+        buf: &'a mut [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        read_fd_bytes(self.stream.fd, buf)
+    }
+}
+
+impl OwnedWriteHalf {
+    pub fn reunite(self, other: OwnedReadHalf) -> Result<TcpStream, ReuniteError> {
+        reunite(other, self)
+    }
+
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        self.stream.local_addr()
+    }
+
+    pub fn write_bytes<'a>(
+        &mut self,
+        buf: &'a [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_fd_bytes(self.stream.fd, buf)
+    }
+}
+
+impl Drop for OwnedWriteHalf {
+    fn drop(&mut self) {
+        if self.shutdown_on_drop {
+            unsafe { libc::shutdown(self.stream.fd, libc::SHUT_WR) };
+        }
+    }
+}
+
+fn reunite(read: OwnedReadHalf, mut write: OwnedWriteHalf) -> Result<TcpStream, ReuniteError> {
+    if !Arc::ptr_eq(&read.stream, &write.stream) {
+        return Err(ReuniteError(read, write));
+    }
+
+    write.shutdown_on_drop = false;
+    drop(write);
+    Ok(Arc::try_unwrap(read.stream).expect("TcpStream: try_unwrap failed in reunite"))
+}
+
+impl Display for ReuniteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "tried to reunite halves that are not from the same socket")
+    }
+}
+
+impl Error for ReuniteError {}
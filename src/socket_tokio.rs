@@ -1,56 +1,75 @@
-use std::io::{Error as IOError, Result as IOResult};
+use std::ffi::c_int;
+use std::io::{IoSlice, Result as IOResult};
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::task::{ready, Context, Poll};
 
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
-use crate::socket::{add_read_fd, add_write_fd, socket_context_get_or_init, TcpStream};
+use crate::bufwrite::BufWriter;
+use crate::io::{AsyncWrite as SlavaAsyncWrite, DuplexStream};
+use crate::socket::{poll_read_fd, poll_shutdown_fd, poll_write_fd, poll_write_vectored_fd, TcpStream};
+use crate::socket_split::{OwnedReadHalf, OwnedWriteHalf, ReadHalf, WriteHalf};
+use crate::unix::UnixStream;
+
+fn poll_read_buf(fd: c_int, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+    let bytes_read = ready!(poll_read_fd(fd, cx, buf.initialize_unfilled()))?;
+    buf.advance(bytes_read);
+    Poll::Ready(Ok(()))
+}
 
 impl AsyncRead for TcpStream {
     fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
-        let local_buf = buf.initialize_unfilled();
-        let bytes_read = unsafe { libc::read(self.fd, local_buf.as_mut_ptr() as *mut _, local_buf.len() as _) };
-        if bytes_read < 0 {
-            let errno = unsafe { *libc::__errno_location() };
-            if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
-                let waker = cx.waker().clone();
-                add_read_fd(self.fd, waker);
-                return Poll::Pending;
-            }
-
-            return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
-        }
-
-        buf.advance(bytes_read as usize);
-        Poll::Ready(Ok(()))
+        poll_read_buf(self.fd, cx, buf)
     }
 }
 
 impl AsyncWrite for TcpStream {
     fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
-        if buf.len() == 0 {
-            return Poll::Ready(Ok(0));
-        }
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
 
-        let bytes_written = unsafe { libc::write(self.fd, buf.as_ptr() as *const _, buf.len() as _) };
-        if bytes_written < 0 {
-            let errno = unsafe { *libc::__errno_location() };
-            if errno == libc::EAGAIN || errno == libc::EWOULDBLOCK {
-                let waker = cx.waker().clone();
-                add_write_fd(self.fd, waker);
-                return Poll::Pending;
-            }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_> ) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.fd)
+    }
+}
 
-            return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
-        }
+impl AsyncRead for UnixStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+        poll_read_buf(self.fd, cx, buf)
+    }
+}
 
-        if bytes_written == 0 {
-            let waker = cx.waker().clone();
-            add_write_fd(self.fd, waker);
-            return Poll::Pending;
-        }
+impl AsyncWrite for UnixStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.fd, cx, bufs)
+    }
 
-        Poll::Ready(Ok(bytes_written as usize))
+    fn is_write_vectored(&self) -> bool {
+        true
     }
 
     fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
@@ -58,12 +77,119 @@ impl AsyncWrite for TcpStream {
     }
 
     fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_> ) -> Poll<IOResult<()>> {
-        unsafe { libc::shutdown(self.fd, libc::SHUT_WR) };
+        poll_shutdown_fd(self.fd)
+    }
+}
+
+impl AsyncRead for ReadHalf<'_> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+        poll_read_buf(self.stream.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for WriteHalf<'_> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.stream.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.stream.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_> ) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.stream.fd)
+    }
+}
 
-        let mut socket_context = socket_context_get_or_init();
-        socket_context.readfds.remove(&self.fd);
-        socket_context.writefds.remove(&self.fd);
+impl AsyncRead for OwnedReadHalf {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+        poll_read_buf(self.stream.fd, cx, buf)
+    }
+}
+
+impl AsyncWrite for OwnedWriteHalf {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        poll_write_fd(self.stream.fd, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        poll_write_vectored_fd(self.stream.fd, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_> ) -> Poll<IOResult<()>> {
+        poll_shutdown_fd(self.stream.fd)
+    }
+}
+
+impl<W: SlavaAsyncWrite + Unpin> AsyncWrite for BufWriter<W> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        SlavaAsyncWrite::poll_write(self, cx, buf)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>]
+    ) -> Poll<IOResult<usize>> {
+        SlavaAsyncWrite::poll_write_vectored(self, cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        SlavaAsyncWrite::poll_flush(self, cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        SlavaAsyncWrite::poll_shutdown(self, cx)
+    }
+}
+
+impl AsyncRead for DuplexStream {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<IOResult<()>> {
+        let bytes_read = ready!(self.poll_read_pipe(cx, buf.initialize_unfilled()))?;
+        buf.advance(bytes_read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for DuplexStream {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IOResult<usize>> {
+        self.poll_write_pipe(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        Poll::Ready(Ok(()))
+    }
 
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IOResult<()>> {
+        self.shutdown_pipe();
         Poll::Ready(Ok(()))
     }
 }
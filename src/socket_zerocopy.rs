@@ -0,0 +1,204 @@
+use std::ffi::c_int;
+use std::fs::File;
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::os::fd::AsRawFd;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::socket::{add_read_fd, add_write_fd, check_os_error, TcpStream};
+
+const SPLICE_CHUNK_SIZE: usize = 64 * 1024;
+
+impl TcpStream {
+    pub fn sendfile<'a>(
+        &mut self,
+        file: &'a File,
+        offset: u64,
+        len: usize
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        sendfile_fd(self.fd, file, offset, len)
+    }
+
+    pub fn splice_to<'a>(
+        &'a self,
+        dst: &'a TcpStream,
+        len: usize
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        splice_fd(self.fd, dst.fd, len)
+    }
+}
+
+pub(crate) fn sendfile_fd(
+    fd: c_int,
+    file: &File,
+    offset: u64,
+    len: usize
+) -> Pin<Box<dyn '_ + Future<Output=IOResult<usize>> + Send + Sync>> {
+    struct SendFileFuture<'b> {
+        fd: c_int,
+        file: &'b File,
+        offset: libc::off_t,
+        remaining: usize,
+        bytes_sent: usize
+    }
+
+    impl<'b> Future for SendFileFuture<'b> {
+        type Output = IOResult<usize>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            while self.remaining > 0 {
+                let mut offset = self.offset;
+                let bytes_sent = unsafe {
+                    libc::sendfile(self.fd, self.file.as_raw_fd(), &mut offset, self.remaining)
+                };
+
+                if bytes_sent == 0 {
+                    return Poll::Ready(Err(IOError::new(
+                        ErrorKind::UnexpectedEof,
+                        "file ended before all requested bytes were sent"
+                    )));
+                }
+
+                if bytes_sent < 0 {
+                    let errno = unsafe { *libc::__errno_location() };
+                    if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                    }
+
+                    let waker = cx.waker().clone();
+                    add_write_fd(self.fd, waker);
+                    return Poll::Pending;
+                }
+
+                self.offset = offset;
+                self.remaining -= bytes_sent as usize;
+                self.bytes_sent += bytes_sent as usize;
+            }
+
+            Poll::Ready(Ok(self.bytes_sent))
+        }
+    }
+
+    let Ok(offset) = libc::off_t::try_from(offset) else {
+        return Box::pin(std::future::ready(Err(IOError::new(ErrorKind::InvalidInput, "offset is too large"))));
+    };
+
+    Box::pin(SendFileFuture { fd, file, offset, remaining: len, bytes_sent: 0 })
+}
+
+pub(crate) fn splice_fd(
+    src_fd: c_int,
+    dst_fd: c_int,
+    len: usize
+) -> Pin<Box<dyn 'static + Future<Output=IOResult<usize>> + Send + Sync>> {
+    struct SpliceFuture {
+        src_fd: c_int,
+        dst_fd: c_int,
+        pipe_fds: [c_int; 2],
+        remaining: usize,
+        in_pipe: usize,
+        bytes_spliced: usize,
+        eof: bool
+    }
+
+    impl Future for SpliceFuture {
+        type Output = IOResult<usize>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            loop {
+                if self.in_pipe > 0 {
+                    let bytes_moved = unsafe {
+                        libc::splice(
+                            self.pipe_fds[0],
+                            std::ptr::null_mut(),
+                            self.dst_fd,
+                            std::ptr::null_mut(),
+                            self.in_pipe,
+                            libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK
+                        )
+                    };
+
+                    if bytes_moved < 0 {
+                        let errno = unsafe { *libc::__errno_location() };
+                        if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                            return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                        }
+
+                        let waker = cx.waker().clone();
+                        add_write_fd(self.dst_fd, waker);
+                        return Poll::Pending;
+                    }
+
+                    if bytes_moved == 0 {
+                        return Poll::Ready(Err(IOError::from(ErrorKind::WriteZero)));
+                    }
+
+                    self.in_pipe -= bytes_moved as usize;
+                    self.bytes_spliced += bytes_moved as usize;
+                    continue;
+                }
+
+                if self.eof || self.remaining == 0 {
+                    return Poll::Ready(Ok(self.bytes_spliced));
+                }
+
+                let bytes_moved = unsafe {
+                    libc::splice(
+                        self.src_fd,
+                        std::ptr::null_mut(),
+                        self.pipe_fds[1],
+                        std::ptr::null_mut(),
+                        self.remaining.min(SPLICE_CHUNK_SIZE),
+                        libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK
+                    )
+                };
+
+                if bytes_moved < 0 {
+                    let errno = unsafe { *libc::__errno_location() };
+                    if errno != libc::EAGAIN && errno != libc::EWOULDBLOCK {
+                        return Poll::Ready(Err(IOError::from_raw_os_error(errno)));
+                    }
+
+                    let waker = cx.waker().clone();
+                    add_read_fd(self.src_fd, waker);
+                    return Poll::Pending;
+                }
+
+                if bytes_moved == 0 {
+                    self.eof = true;
+                    continue;
+                }
+
+                self.remaining -= bytes_moved as usize;
+                self.in_pipe += bytes_moved as usize;
+            }
+        }
+    }
+
+    impl Drop for SpliceFuture {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.pipe_fds[0]);
+                libc::close(self.pipe_fds[1]);
+            }
+        }
+    }
+
+    let mut pipe_fds = [0 as c_int; 2];
+    if let Err(e) = check_os_error(unsafe {
+        libc::pipe2(pipe_fds.as_mut_ptr(), libc::O_NONBLOCK | libc::O_CLOEXEC)
+    }) {
+        return Box::pin(std::future::ready(Err(e)));
+    }
+
+    Box::pin(SpliceFuture {
+        src_fd,
+        dst_fd,
+        pipe_fds,
+        remaining: len,
+        in_pipe: 0,
+        bytes_spliced: 0,
+        eof: false
+    })
+}
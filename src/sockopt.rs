@@ -0,0 +1,172 @@
+use std::ffi::c_int;
+use std::io::Result as IOResult;
+use std::time::Duration;
+
+use crate::socket::check_os_error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TcpKeepalive {
+    pub idle: Option<Duration>,
+    pub interval: Option<Duration>,
+    pub count: Option<u32>
+}
+
+pub(crate) fn setsockopt<T>(fd: c_int, level: c_int, name: c_int, value: T) -> IOResult<()> {
+    check_os_error(unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const T as *const _,
+            std::mem::size_of::<T>() as libc::socklen_t
+        )
+    })?;
+    Ok(())
+}
+
+pub(crate) fn getsockopt<T: Copy>(fd: c_int, level: c_int, name: c_int) -> IOResult<T> {
+    let mut value: T = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<T>() as libc::socklen_t;
+    check_os_error(unsafe {
+        libc::getsockopt(fd, level, name, &mut value as *mut T as *mut _, &mut len)
+    })?;
+    Ok(value)
+}
+
+fn set_flag(fd: c_int, level: c_int, name: c_int, value: bool) -> IOResult<()> {
+    setsockopt::<c_int>(fd, level, name, value as c_int)
+}
+
+fn get_flag(fd: c_int, level: c_int, name: c_int) -> IOResult<bool> {
+    Ok(getsockopt::<c_int>(fd, level, name)? != 0)
+}
+
+pub(crate) fn set_reuseaddr(fd: c_int, reuseaddr: bool) -> IOResult<()> {
+    set_flag(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, reuseaddr)
+}
+
+pub(crate) fn reuseaddr(fd: c_int) -> IOResult<bool> {
+    get_flag(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR)
+}
+
+pub(crate) fn set_reuseport(fd: c_int, reuseport: bool) -> IOResult<()> {
+    set_flag(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT, reuseport)
+}
+
+pub(crate) fn reuseport(fd: c_int) -> IOResult<bool> {
+    get_flag(fd, libc::SOL_SOCKET, libc::SO_REUSEPORT)
+}
+
+pub(crate) fn set_nodelay(fd: c_int, nodelay: bool) -> IOResult<()> {
+    set_flag(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY, nodelay)
+}
+
+pub(crate) fn nodelay(fd: c_int) -> IOResult<bool> {
+    get_flag(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY)
+}
+
+pub(crate) fn set_cork(fd: c_int, cork: bool) -> IOResult<()> {
+    set_flag(fd, libc::IPPROTO_TCP, libc::TCP_CORK, cork)
+}
+
+pub(crate) fn cork(fd: c_int) -> IOResult<bool> {
+    get_flag(fd, libc::IPPROTO_TCP, libc::TCP_CORK)
+}
+
+pub(crate) fn set_keepalive(fd: c_int, keepalive: Option<TcpKeepalive>) -> IOResult<()> {
+    let Some(keepalive) = keepalive else {
+        return set_flag(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, false);
+    };
+
+    set_flag(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, true)?;
+    if let Some(idle) = keepalive.idle {
+        setsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, duration_to_secs(idle))?;
+    }
+    if let Some(interval) = keepalive.interval {
+        setsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL, duration_to_secs(interval))?;
+    }
+    if let Some(count) = keepalive.count {
+        setsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT, count.min(c_int::MAX as u32) as c_int)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn keepalive(fd: c_int) -> IOResult<Option<TcpKeepalive>> {
+    if !get_flag(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE)? {
+        return Ok(None);
+    }
+
+    let idle = getsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE)?;
+    let interval = getsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPINTVL)?;
+    let count = getsockopt::<c_int>(fd, libc::IPPROTO_TCP, libc::TCP_KEEPCNT)?;
+    Ok(Some(TcpKeepalive {
+        idle: Some(Duration::from_secs(idle as u64)),
+        interval: Some(Duration::from_secs(interval as u64)),
+        count: Some(count as u32)
+    }))
+}
+
+pub(crate) fn set_linger(fd: c_int, linger: Option<Duration>) -> IOResult<()> {
+    let linger = libc::linger {
+        l_onoff: linger.is_some() as c_int,
+        l_linger: linger.map(duration_to_secs).unwrap_or(0)
+    };
+    setsockopt(fd, libc::SOL_SOCKET, libc::SO_LINGER, linger)
+}
+
+pub(crate) fn linger(fd: c_int) -> IOResult<Option<Duration>> {
+    let linger = getsockopt::<libc::linger>(fd, libc::SOL_SOCKET, libc::SO_LINGER)?;
+    if linger.l_onoff == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(Duration::from_secs(linger.l_linger as u64)))
+    }
+}
+
+pub(crate) fn set_recv_buffer_size(fd: c_int, size: u32) -> IOResult<()> {
+    setsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_RCVBUF, size.min(c_int::MAX as u32) as c_int)
+}
+
+pub(crate) fn recv_buffer_size(fd: c_int) -> IOResult<u32> {
+    Ok(getsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_RCVBUF)? as u32)
+}
+
+pub(crate) fn set_send_buffer_size(fd: c_int, size: u32) -> IOResult<()> {
+    setsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_SNDBUF, size.min(c_int::MAX as u32) as c_int)
+}
+
+pub(crate) fn send_buffer_size(fd: c_int) -> IOResult<u32> {
+    Ok(getsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_SNDBUF)? as u32)
+}
+
+pub(crate) fn set_ttl(fd: c_int, ttl: u32) -> IOResult<()> {
+    let ttl = ttl.min(c_int::MAX as u32) as c_int;
+    if getsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_DOMAIN)? == libc::AF_INET6 {
+        setsockopt::<c_int>(fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS, ttl)
+    } else {
+        setsockopt::<c_int>(fd, libc::IPPROTO_IP, libc::IP_TTL, ttl)
+    }
+}
+
+pub(crate) fn ttl(fd: c_int) -> IOResult<u32> {
+    let ttl = if getsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_DOMAIN)? == libc::AF_INET6 {
+        getsockopt::<c_int>(fd, libc::IPPROTO_IPV6, libc::IPV6_UNICAST_HOPS)?
+    } else {
+        getsockopt::<c_int>(fd, libc::IPPROTO_IP, libc::IP_TTL)?
+    };
+    Ok(ttl as u32)
+}
+
+pub(crate) fn take_error(fd: c_int) -> IOResult<Option<std::io::Error>> {
+    let errno = getsockopt::<c_int>(fd, libc::SOL_SOCKET, libc::SO_ERROR)?;
+    if errno == 0 {
+        Ok(None)
+    } else {
+        Ok(Some(std::io::Error::from_raw_os_error(errno)))
+    }
+}
+
+fn duration_to_secs(duration: Duration) -> c_int {
+    let secs = duration.as_secs().saturating_add((duration.subsec_nanos() > 0) as u64);
+    secs.min(c_int::MAX as u64) as c_int
+}
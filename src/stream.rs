@@ -0,0 +1,645 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+use crate::time::{self, Sleep};
+
+pub trait Stream {
+    type Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+}
+
+impl<S: Stream + Unpin + ?Sized> Stream for &mut S {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut **self).poll_next(cx)
+    }
+}
+
+impl<S: Stream + ?Sized> Stream for Pin<Box<S>> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().as_mut().poll_next(cx)
+    }
+}
+
+pub trait StreamExt: Stream {
+    fn next(&mut self) -> Next<'_, Self> where Self: Unpin {
+        Next { stream: self }
+    }
+
+    fn map<T, F>(self, f: F) -> Map<Self, F>
+        where F: FnMut(Self::Item) -> T,
+              Self: Sized
+    {
+        Map { stream: Box::pin(self), f }
+    }
+
+    fn filter<P>(self, predicate: P) -> Filter<Self, P>
+        where P: FnMut(&Self::Item) -> bool,
+              Self: Sized
+    {
+        Filter { stream: Box::pin(self), predicate }
+    }
+
+    fn take(self, n: usize) -> Take<Self> where Self: Sized {
+        Take { stream: Box::pin(self), remaining: n }
+    }
+
+    fn timeout(self, duration: Duration) -> Timeout<Self> where Self: Sized {
+        Timeout { stream: Box::pin(self), duration, sleep: None }
+    }
+
+    fn buffer_unordered(self, limit: usize) -> BufferUnordered<Self>
+        where Self::Item: Future,
+              Self: Sized
+    {
+        BufferUnordered {
+            stream: Box::pin(self),
+            in_flight: InFlight::new(),
+            limit: limit.max(1),
+            done: false
+        }
+    }
+
+    fn for_each_concurrent<Fut, F>(self, limit: Option<usize>, f: F) -> ForEachConcurrent<Self, F, Fut>
+        where F: FnMut(Self::Item) -> Fut,
+              Fut: Future<Output = ()>,
+              Self: Sized
+    {
+        ForEachConcurrent {
+            stream: Box::pin(self),
+            f,
+            in_flight: InFlight::new(),
+            limit: limit.map(|limit| limit.max(1)),
+            done: false
+        }
+    }
+}
+
+impl<S: Stream + ?Sized> StreamExt for S {}
+
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S
+}
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+pub struct Map<S, F> {
+    stream: Pin<Box<S>>,
+    f: F
+}
+
+impl<S, F> Unpin for Map<S, F> {}
+
+impl<T, S: Stream, F: FnMut(S::Item) -> T> Stream for Map<S, F> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        this.stream.as_mut().poll_next(cx).map(|item| item.map(&mut this.f))
+    }
+}
+
+pub struct Filter<S, P> {
+    stream: Pin<Box<S>>,
+    predicate: P
+}
+
+impl<S, P> Unpin for Filter<S, P> {}
+
+impl<S: Stream, P: FnMut(&S::Item) -> bool> Stream for Filter<S, P> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                Some(item) if (this.predicate)(&item) => return Poll::Ready(Some(item)),
+                Some(_) => continue,
+                None => return Poll::Ready(None)
+            }
+        }
+    }
+}
+
+pub struct Take<S> {
+    stream: Pin<Box<S>>,
+    remaining: usize
+}
+
+impl<S: Stream> Stream for Take<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let item = ready!(self.stream.as_mut().poll_next(cx));
+        match item {
+            Some(_) => self.remaining -= 1,
+            None => self.remaining = 0
+        }
+        Poll::Ready(item)
+    }
+}
+
+pub struct Timeout<S> {
+    stream: Pin<Box<S>>,
+    duration: Duration,
+    sleep: Option<Sleep>
+}
+
+impl<S: Stream> Stream for Timeout<S> {
+    type Item = IOResult<S::Item>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Poll::Ready(item) = self.stream.as_mut().poll_next(cx) {
+            self.sleep = None;
+            return Poll::Ready(item.map(Ok));
+        }
+
+        let duration = self.duration;
+        let sleep = self.sleep.get_or_insert_with(|| time::sleep(duration));
+        ready!(Pin::new(sleep).poll(cx));
+        self.sleep = None;
+        Poll::Ready(Some(Err(IOError::new(ErrorKind::TimedOut, "stream item timed out"))))
+    }
+}
+
+struct ReadyQueue {
+    ready: VecDeque<usize>,
+    waker: Option<Waker>
+}
+
+struct SlotWaker {
+    slot: usize,
+    queue: Arc<Mutex<ReadyQueue>>
+}
+
+impl Wake for SlotWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.ready.push_back(self.slot);
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+struct InFlight<F> {
+    slots: Vec<Option<(Pin<Box<F>>, Waker)>>,
+    free: Vec<usize>,
+    len: usize,
+    queue: Arc<Mutex<ReadyQueue>>
+}
+
+impl<F: Future> InFlight<F> {
+    fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+            queue: Arc::new(Mutex::new(ReadyQueue { ready: VecDeque::new(), waker: None }))
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn push(&mut self, fut: F) {
+        let slot = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(None);
+            self.slots.len() - 1
+        });
+        let waker = Waker::from(Arc::new(SlotWaker { slot, queue: self.queue.clone() }));
+        self.slots[slot] = Some((Box::pin(fut), waker));
+        self.queue.lock().unwrap().ready.push_back(slot);
+        self.len += 1;
+    }
+
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<F::Output>> {
+        let mut budget = {
+            let mut queue = self.queue.lock().unwrap();
+            queue.waker = Some(cx.waker().clone());
+            queue.ready.len()
+        };
+
+        while budget > 0 {
+            budget -= 1;
+            let Some(slot) = self.queue.lock().unwrap().ready.pop_front() else {
+                break;
+            };
+            let Some((fut, waker)) = &mut self.slots[slot] else {
+                continue;
+            };
+
+            if let Poll::Ready(output) = fut.as_mut().poll(&mut Context::from_waker(waker)) {
+                self.slots[slot] = None;
+                self.free.push(slot);
+                self.len -= 1;
+                return Poll::Ready(Some(output));
+            }
+        }
+
+        if self.is_empty() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
+
+pub struct BufferUnordered<S: Stream> where S::Item: Future {
+    stream: Pin<Box<S>>,
+    in_flight: InFlight<S::Item>,
+    limit: usize,
+    done: bool
+}
+
+impl<S: Stream> Stream for BufferUnordered<S> where S::Item: Future {
+    type Item = <S::Item as Future>::Output;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while !this.done && this.in_flight.len() < this.limit {
+            match this.stream.as_mut().poll_next(cx) {
+                Poll::Ready(Some(fut)) => this.in_flight.push(fut),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break
+            }
+        }
+
+        match this.in_flight.poll_next(cx) {
+            Poll::Ready(Some(output)) => Poll::Ready(Some(output)),
+            Poll::Ready(None) if this.done => Poll::Ready(None),
+            _ => Poll::Pending
+        }
+    }
+}
+
+pub struct ForEachConcurrent<S, F, Fut> {
+    stream: Pin<Box<S>>,
+    f: F,
+    in_flight: InFlight<Fut>,
+    limit: Option<usize>,
+    done: bool
+}
+
+impl<S, F, Fut> Unpin for ForEachConcurrent<S, F, Fut> {}
+
+impl<S, F, Fut> Future for ForEachConcurrent<S, F, Fut>
+    where S: Stream,
+          F: FnMut(S::Item) -> Fut,
+          Fut: Future<Output = ()>
+{
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        loop {
+            let mut made_progress = false;
+            while !this.done && this.limit.is_none_or(|limit| this.in_flight.len() < limit) {
+                match this.stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(item)) => {
+                        this.in_flight.push((this.f)(item));
+                        made_progress = true;
+                    },
+                    Poll::Ready(None) => this.done = true,
+                    Poll::Pending => break
+                }
+            }
+
+            while let Poll::Ready(Some(())) = this.in_flight.poll_next(cx) {
+                made_progress = true;
+            }
+
+            if this.done && this.in_flight.is_empty() {
+                return Poll::Ready(());
+            }
+            if !made_progress {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+pub trait Sink<Item> {
+    type Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error>;
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>>;
+}
+
+impl<Item, S: Sink<Item> + Unpin + ?Sized> Sink<Item> for &mut S {
+    type Error = S::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut **self).poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        Pin::new(&mut **self).start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut **self).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::new(&mut **self).poll_close(cx)
+    }
+}
+
+pub trait SinkExt<Item>: Sink<Item> {
+    fn send(&mut self, item: Item) -> SinkSend<'_, Self, Item> where Self: Unpin {
+        SinkSend { sink: self, item: Some(item) }
+    }
+
+    fn flush(&mut self) -> SinkFlush<'_, Self, Item> where Self: Unpin {
+        SinkFlush { sink: self, _item: PhantomData }
+    }
+
+    fn close(&mut self) -> SinkClose<'_, Self, Item> where Self: Unpin {
+        SinkClose { sink: self, _item: PhantomData }
+    }
+}
+
+impl<Item, S: Sink<Item> + ?Sized> SinkExt<Item> for S {}
+
+pub struct SinkSend<'a, S: ?Sized, Item> {
+    sink: &'a mut S,
+    item: Option<Item>
+}
+
+impl<S: ?Sized, Item> Unpin for SinkSend<'_, S, Item> {}
+
+impl<Item, S: Sink<Item> + Unpin + ?Sized> Future for SinkSend<'_, S, Item> {
+    type Output = Result<(), S::Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.item.is_some() {
+            ready!(Pin::new(&mut *this.sink).poll_ready(cx))?;
+            if let Some(item) = this.item.take() {
+                Pin::new(&mut *this.sink).start_send(item)?;
+            }
+        }
+        Pin::new(&mut *this.sink).poll_flush(cx)
+    }
+}
+
+pub struct SinkFlush<'a, S: ?Sized, Item> {
+    sink: &'a mut S,
+    _item: PhantomData<fn(Item)>
+}
+
+impl<Item, S: Sink<Item> + Unpin + ?Sized> Future for SinkFlush<'_, S, Item> {
+    type Output = Result<(), S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.sink).poll_flush(cx)
+    }
+}
+
+pub struct SinkClose<'a, S: ?Sized, Item> {
+    sink: &'a mut S,
+    _item: PhantomData<fn(Item)>
+}
+
+impl<Item, S: Sink<Item> + Unpin + ?Sized> Future for SinkClose<'_, S, Item> {
+    type Output = Result<(), S::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.sink).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::future::poll_fn;
+    use std::io::ErrorKind;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::block_on;
+    use crate::codec::{Framed, LinesCodec};
+    use crate::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    struct Iter<I> {
+        iter: I
+    }
+
+    impl<I: Iterator + Unpin> Stream for Iter<I> {
+        type Item = I::Item;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            Poll::Ready(self.iter.next())
+        }
+    }
+
+    fn iter<I: IntoIterator>(iter: I) -> Iter<I::IntoIter> {
+        Iter { iter: iter.into_iter() }
+    }
+
+    struct Tracker {
+        active: AtomicUsize,
+        peak: AtomicUsize
+    }
+
+    impl Tracker {
+        fn new() -> Arc<Self> {
+            Arc::new(Self { active: AtomicUsize::new(0), peak: AtomicUsize::new(0) })
+        }
+
+        async fn run(self: Arc<Self>, delay_ms: u64) {
+            self.peak.fetch_max(self.active.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+            time::sleep(Duration::from_millis(delay_ms)).await;
+            self.active.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn buffer_unordered_respects_limit_and_yields_out_of_order() {
+        let tracker = Tracker::new();
+        let tracker1 = tracker.clone();
+        let outputs = block_on(async move {
+            let delays = [120, 10, 40, 10];
+            let mut stream = iter(delays.into_iter().enumerate()).map(move |(idx, delay_ms)| {
+                let tracker = tracker1.clone();
+                async move {
+                    tracker.run(delay_ms).await;
+                    idx
+                }
+            }).buffer_unordered(2);
+
+            let mut outputs = Vec::new();
+            while let Some(idx) = stream.next().await {
+                outputs.push(idx);
+            }
+            outputs
+        });
+        assert_eq!(outputs, [1, 2, 3, 0]);
+        assert_eq!(tracker.peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn buffer_unordered_drains_in_flight_after_stream_ends() {
+        let outputs = block_on(async {
+            let (mut client, server) = duplex(64);
+            let lines = Framed::new(server, LinesCodec::new()).map(|line| async move {
+                let line = line.unwrap();
+                time::sleep(Duration::from_millis(if line == "slow" { 50 } else { 1 })).await;
+                line
+            });
+            let mut stream = lines.buffer_unordered(4);
+
+            client.write_all(b"slow\nfast\n").await.unwrap();
+            client.shutdown().await.unwrap();
+
+            let mut outputs = Vec::new();
+            while let Some(line) = stream.next().await {
+                outputs.push(line);
+            }
+            outputs
+        });
+        assert_eq!(outputs, ["fast", "slow"]);
+    }
+
+    #[test]
+    fn buffer_unordered_polls_only_woken_futures() {
+        let polls = Arc::new(AtomicUsize::new(0));
+        let polls1 = polls.clone();
+        block_on(async move {
+            let mut stream = iter([0, 1]).map(move |idx| {
+                let polls = polls1.clone();
+                let mut sleep = time::sleep(Duration::from_millis(20));
+                poll_fn(move |cx| {
+                    if idx == 0 {
+                        polls.fetch_add(1, Ordering::SeqCst);
+                        return Poll::Pending;
+                    }
+                    Pin::new(&mut sleep).poll(cx).map(|_| idx)
+                })
+            }).buffer_unordered(2);
+
+            assert_eq!(stream.next().await, Some(1));
+        });
+        assert_eq!(polls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn for_each_concurrent_respects_limit() {
+        let tracker = Tracker::new();
+        let completed = Arc::new(AtomicUsize::new(0));
+        let (tracker1, completed1) = (tracker.clone(), completed.clone());
+        block_on(async move {
+            iter([30, 10, 20, 10, 5]).for_each_concurrent(Some(3), move |delay_ms| {
+                let (tracker, completed) = (tracker1.clone(), completed1.clone());
+                async move {
+                    tracker.run(delay_ms).await;
+                    completed.fetch_add(1, Ordering::SeqCst);
+                }
+            }).await;
+        });
+        assert_eq!(completed.load(Ordering::SeqCst), 5);
+        assert_eq!(tracker.peak.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn timeout_fires_and_resets() {
+        block_on(async {
+            let (mut client, server) = duplex(64);
+            let mut lines = Framed::new(server, LinesCodec::new()).timeout(Duration::from_millis(50));
+
+            assert_eq!(lines.next().await.unwrap().unwrap_err().kind(), ErrorKind::TimedOut);
+            client.write_all(b"late\n").await.unwrap();
+            assert_eq!(lines.next().await.unwrap().unwrap().unwrap(), "late");
+            assert_eq!(lines.next().await.unwrap().unwrap_err().kind(), ErrorKind::TimedOut);
+
+            drop(client);
+            assert!(lines.next().await.is_none());
+        });
+    }
+
+    #[test]
+    fn sink_send_waits_for_capacity() {
+        block_on(async {
+            let (client, mut server) = duplex(4);
+            let mut writer = Framed::new(client, LinesCodec::new());
+
+            let mut send = writer.send("hello");
+            assert!(poll_fn(|cx| Poll::Ready(Pin::new(&mut send).poll(cx).is_pending())).await);
+
+            let mut received = [0u8; 6];
+            server.read_exact(&mut received[..4]).await.unwrap();
+            send.await.unwrap();
+            server.read_exact(&mut received[4..]).await.unwrap();
+            assert_eq!(&received, b"hello\n");
+        });
+    }
+
+    #[test]
+    fn sink_flush_and_close() {
+        block_on(async {
+            let (client, mut server) = duplex(64);
+            let mut writer = Framed::new(client, LinesCodec::new());
+
+            Pin::new(&mut writer).start_send("a").unwrap();
+            assert_eq!(writer.write_buffer(), b"a\n");
+            SinkExt::<&str>::flush(&mut writer).await.unwrap();
+            assert!(writer.write_buffer().is_empty());
+
+            Pin::new(&mut writer).start_send("b").unwrap();
+            SinkExt::<&str>::close(&mut writer).await.unwrap();
+
+            let mut received = Vec::new();
+            let mut buf = [0u8; 8];
+            loop {
+                match server.read(&mut buf).await.unwrap() {
+                    0 => break,
+                    n => received.extend_from_slice(&buf[..n])
+                }
+            }
+            assert_eq!(received, b"a\nb\n");
+        });
+    }
+
+    #[test]
+    fn sink_send_reports_closed_peer() {
+        block_on(async {
+            let (client, server) = duplex(64);
+            let mut writer = Framed::new(client, LinesCodec::new());
+            drop(server);
+            assert_eq!(writer.send("lost").await.unwrap_err().kind(), ErrorKind::BrokenPipe);
+        });
+    }
+}
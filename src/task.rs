@@ -0,0 +1,217 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::future::Future;
+use std::io::Error as IOError;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::blocking::run_blocking;
+
+pub struct JoinError {
+    panic: Option<Box<dyn Any + Send>>
+}
+
+impl JoinError {
+    pub fn is_panic(&self) -> bool {
+        self.panic.is_some()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.panic.is_none()
+    }
+
+    pub fn try_into_panic(self) -> Result<Box<dyn Any + Send>, JoinError> {
+        match self.panic {
+            Some(panic) => Ok(panic),
+            None => Err(self)
+        }
+    }
+
+    fn panic_message(&self) -> Option<&str> {
+        let panic = self.panic.as_ref()?;
+        panic.downcast_ref::<&str>().copied().or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+    }
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match (&self.panic, self.panic_message()) {
+            (None, _) => write!(f, "task was cancelled"),
+            (Some(_), Some(message)) => write!(f, "task panicked: {}", message),
+            (Some(_), None) => write!(f, "task panicked")
+        }
+    }
+}
+
+impl Debug for JoinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "JoinError({})", self)
+    }
+}
+
+impl Error for JoinError {}
+
+impl From<JoinError> for IOError {
+    fn from(e: JoinError) -> Self {
+        IOError::other(e.to_string())
+    }
+}
+
+struct JoinState<T> {
+    result: Option<Result<T, JoinError>>,
+    finished: bool,
+    waker: Option<Waker>
+}
+
+pub struct JoinHandle<T> {
+    state: Arc<Mutex<JoinState<T>>>
+}
+
+pub(crate) struct JoinSender<T> {
+    state: Arc<Mutex<JoinState<T>>>
+}
+
+pub(crate) fn join_channel<T>() -> (JoinSender<T>, JoinHandle<T>) {
+    let state = Arc::new(Mutex::new(JoinState { result: None, finished: false, waker: None }));
+    (JoinSender { state: state.clone() }, JoinHandle { state })
+}
+
+pub fn spawn_blocking<T, F>(f: F) -> JoinHandle<T>
+    where T: Send + 'static,
+          F: FnOnce() -> T + Send + 'static
+{
+    run_blocking(f)
+}
+
+pub fn block_in_place<F, R>(f: F) -> R
+    where F: FnOnce() -> R
+{
+    crate::block_in_place(f)
+}
+
+impl<T> JoinSender<T> {
+    pub(crate) fn send(self, result: T) {
+        self.finish(Ok(result));
+    }
+
+    pub(crate) fn send_panic(self, panic: Box<dyn Any + Send>) {
+        self.finish(Err(JoinError { panic: Some(panic) }));
+    }
+
+    fn finish(&self, result: Result<T, JoinError>) {
+        let mut state = self.state.lock().unwrap();
+        if state.finished {
+            return;
+        }
+
+        state.result = Some(result);
+        state.finished = true;
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Drop for JoinSender<T> {
+    fn drop(&mut self) {
+        self.finish(Err(JoinError { panic: None }));
+    }
+}
+
+impl<T> JoinHandle<T> {
+    pub fn is_finished(&self) -> bool {
+        self.state.lock().unwrap().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(result) = state.result.take() {
+            return Poll::Ready(result);
+        }
+
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+    use std::thread::spawn as thread_spawn;
+
+    use super::*;
+    use crate::{block_on, Slava};
+
+    #[test]
+    fn spawn_blocking_reports_panics() {
+        let (ok, panicked) = block_on(async {
+            let panicked = spawn_blocking(|| -> u32 { panic!("blocking boom") }).await;
+            let ok = spawn_blocking(|| 42).await;
+            (ok, panicked)
+        });
+        assert_eq!(ok.unwrap(), 42);
+
+        let e = panicked.unwrap_err();
+        assert!(e.is_panic());
+        assert_eq!(e.to_string(), "task panicked: blocking boom");
+        assert_eq!(IOError::from(e).kind(), std::io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn task_panic_does_not_stop_worker() {
+        let slava = Slava::slava();
+        let panicked = slava.spawn_with_handle(async { panic!("task boom") });
+        let (sender, receiver) = channel();
+        slava.spawn(async move {
+            let _ = sender.send(panicked.await);
+        });
+
+        let runtime = slava.clone();
+        thread_spawn(move || runtime.run(1));
+        let e = receiver.recv().unwrap().unwrap_err();
+        assert!(e.is_panic());
+        assert!(e.try_into_panic().is_ok());
+
+        let handle = slava.spawn_with_handle(async { 7 });
+        let (sender, receiver) = channel();
+        slava.spawn(async move {
+            let _ = sender.send(handle.await.unwrap());
+        });
+        assert_eq!(receiver.recv().unwrap(), 7);
+    }
+
+    #[test]
+    fn block_in_place_lets_worker_queue_progress() {
+        let slava = Slava::slava();
+        let (ready_sender, ready) = channel();
+        let (done_sender, done) = channel();
+        slava.spawn(async move {
+            let received = block_in_place(|| ready.recv_timeout(std::time::Duration::from_secs(5)));
+            let _ = done_sender.send(received);
+        });
+        slava.spawn(async move {
+            let _ = ready_sender.send(());
+        });
+
+        let runtime = slava.clone();
+        thread_spawn(move || runtime.run(1));
+        assert!(done.recv().unwrap().is_ok());
+    }
+
+    #[test]
+    fn dropped_sender_cancels_handle() {
+        let (sender, handle) = join_channel::<u32>();
+        drop(sender);
+        assert!(handle.is_finished());
+
+        let e = block_on(handle).unwrap_err();
+        assert!(e.is_cancelled());
+        assert_eq!(e.to_string(), "task was cancelled");
+    }
+}
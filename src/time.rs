@@ -0,0 +1,83 @@
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::socket::{add_timer, remove_timer, update_timer};
+
+#[derive(Debug)]
+pub struct Sleep {
+    deadline: Instant,
+    timer_key: Option<(Instant, u64)>
+}
+
+pub fn sleep(duration: Duration) -> Sleep {
+    sleep_until(Instant::now() + duration)
+}
+
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep { deadline, timer_key: None }
+}
+
+impl Sleep {
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    pub fn is_elapsed(&self) -> bool {
+        Instant::now() >= self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_elapsed() {
+            if let Some(timer_key) = self.timer_key.take() {
+                remove_timer(timer_key);
+            }
+            return Poll::Ready(());
+        }
+
+        let waker = cx.waker().clone();
+        match self.timer_key {
+            Some(timer_key) => update_timer(timer_key, waker),
+            None => self.timer_key = Some(add_timer(self.deadline, waker))
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(timer_key) = self.timer_key.take() {
+            remove_timer(timer_key);
+        }
+    }
+}
+
+pub struct Timeout<F: Future> {
+    future: Pin<Box<F>>,
+    sleep: Sleep
+}
+
+pub fn timeout<F: Future>(duration: Duration, future: F) -> Timeout<F> {
+    Timeout { future: Box::pin(future), sleep: sleep(duration) }
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = IOResult<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+
+        match Pin::new(&mut self.sleep).poll(cx) {
+            Poll::Ready(()) => Poll::Ready(Err(IOError::new(ErrorKind::TimedOut, "operation timed out"))),
+            Poll::Pending => Poll::Pending
+        }
+    }
+}
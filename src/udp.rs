@@ -0,0 +1,212 @@
+use std::ffi::c_int;
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, RawFd};
+use std::pin::Pin;
+
+use crate::socket::{
+    check_os_error,
+    check_os_error_size,
+    getpeername,
+    getsockname,
+    new_socket,
+    remove_fd,
+    socket_addr_to_sockaddr,
+    sockaddr_to_socket_addr,
+    IoFuture
+};
+use crate::sockopt;
+
+#[derive(Debug)]
+pub struct UdpSocket {
+    fd: c_int
+}
+
+impl UdpSocket {
+    pub fn bind(addr: SocketAddr) -> IOResult<Self> {
+        let domain = match addr {
+            SocketAddr::V4(_) => libc::AF_INET,
+            SocketAddr::V6(_) => libc::AF_INET6
+        };
+
+        let socket = Self { fd: new_socket(domain, libc::SOCK_DGRAM)? };
+
+        let (storage, len) = socket_addr_to_sockaddr(&addr);
+        check_os_error(unsafe { libc::bind(socket.fd, &storage as *const _ as *const _, len) })?;
+        Ok(socket)
+    }
+
+    pub fn connect(&self, addr: SocketAddr) -> IOResult<()> {
+        let (storage, len) = socket_addr_to_sockaddr(&addr);
+        check_os_error(unsafe { libc::connect(self.fd, &storage as *const _ as *const _, len) })?;
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> IOResult<SocketAddr> {
+        getsockname(self.fd)
+    }
+
+    pub fn peer_addr(&self) -> IOResult<SocketAddr> {
+        getpeername(self.fd)
+    }
+
+    pub fn send_to<'a>(
+        &self,
+        buf: &'a [u8],
+        addr: SocketAddr
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        let (storage, len) = socket_addr_to_sockaddr(&addr);
+        Box::pin(IoFuture::write(fd, move || check_os_error_size(unsafe {
+            libc::sendto(
+                fd,
+                buf.as_ptr() as *const _,
+                buf.len(),
+                libc::MSG_NOSIGNAL,
+                &storage as *const _ as *const _,
+                len
+            )
+        })))
+    }
+
+    pub fn send<'a>(&self, buf: &'a [u8]) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::write(fd, move || check_os_error_size(unsafe {
+            libc::send(fd, buf.as_ptr() as *const _, buf.len(), libc::MSG_NOSIGNAL)
+        })))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn recv_from<'a>(
+        &self,
+        buf: &'a mut [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<(usize, SocketAddr)>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || recv_from(fd, buf, 0)))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn peek_from<'a>(
+        &self,
+        buf: &'a mut [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<(usize, SocketAddr)>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || recv_from(fd, buf, libc::MSG_PEEK)))
+    }
+
+    pub fn recv<'a>(&self, buf: &'a mut [u8]) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || check_os_error_size(unsafe {
+            libc::recv(fd, buf.as_mut_ptr() as *mut _, buf.len(), 0)
+        })))
+    }
+
+    pub fn set_broadcast(&self, broadcast: bool) -> IOResult<()> {
+        sockopt::setsockopt::<c_int>(self.fd, libc::SOL_SOCKET, libc::SO_BROADCAST, broadcast as c_int)
+    }
+
+    pub fn broadcast(&self) -> IOResult<bool> {
+        Ok(sockopt::getsockopt::<c_int>(self.fd, libc::SOL_SOCKET, libc::SO_BROADCAST)? != 0)
+    }
+
+    pub fn set_ttl(&self, ttl: u32) -> IOResult<()> {
+        sockopt::set_ttl(self.fd, ttl)
+    }
+
+    pub fn ttl(&self) -> IOResult<u32> {
+        sockopt::ttl(self.fd)
+    }
+
+    pub fn set_multicast_loop_v4(&self, multicast_loop: bool) -> IOResult<()> {
+        sockopt::setsockopt::<c_int>(self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP, multicast_loop as c_int)
+    }
+
+    pub fn multicast_loop_v4(&self) -> IOResult<bool> {
+        Ok(sockopt::getsockopt::<c_int>(self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_LOOP)? != 0)
+    }
+
+    pub fn set_multicast_ttl_v4(&self, ttl: u32) -> IOResult<()> {
+        sockopt::setsockopt::<c_int>(self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL, ttl.min(255) as c_int)
+    }
+
+    pub fn multicast_ttl_v4(&self) -> IOResult<u32> {
+        Ok(sockopt::getsockopt::<c_int>(self.fd, libc::IPPROTO_IP, libc::IP_MULTICAST_TTL)? as u32)
+    }
+
+    pub fn set_multicast_loop_v6(&self, multicast_loop: bool) -> IOResult<()> {
+        sockopt::setsockopt::<c_int>(self.fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_LOOP, multicast_loop as c_int)
+    }
+
+    pub fn multicast_loop_v6(&self) -> IOResult<bool> {
+        Ok(sockopt::getsockopt::<c_int>(self.fd, libc::IPPROTO_IPV6, libc::IPV6_MULTICAST_LOOP)? != 0)
+    }
+
+    pub fn join_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> IOResult<()> {
+        sockopt::setsockopt(self.fd, libc::IPPROTO_IP, libc::IP_ADD_MEMBERSHIP, ip_mreq(multiaddr, interface))
+    }
+
+    pub fn leave_multicast_v4(&self, multiaddr: Ipv4Addr, interface: Ipv4Addr) -> IOResult<()> {
+        sockopt::setsockopt(self.fd, libc::IPPROTO_IP, libc::IP_DROP_MEMBERSHIP, ip_mreq(multiaddr, interface))
+    }
+
+    pub fn join_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> IOResult<()> {
+        sockopt::setsockopt(self.fd, libc::IPPROTO_IPV6, libc::IPV6_ADD_MEMBERSHIP, ipv6_mreq(multiaddr, interface))
+    }
+
+    pub fn leave_multicast_v6(&self, multiaddr: Ipv6Addr, interface: u32) -> IOResult<()> {
+        sockopt::setsockopt(self.fd, libc::IPPROTO_IPV6, libc::IPV6_DROP_MEMBERSHIP, ipv6_mreq(multiaddr, interface))
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.fd)
+    }
+}
+
+impl AsRawFd for UdpSocket {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for UdpSocket {
+    fn drop(&mut self) {
+        remove_fd(self.fd);
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+fn recv_from(fd: c_int, buf: &mut [u8], flags: c_int) -> IOResult<(usize, SocketAddr)> {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&storage) as libc::socklen_t;
+    let bytes_read = check_os_error_size(unsafe {
+        libc::recvfrom(
+            fd,
+            buf.as_mut_ptr() as *mut _,
+            buf.len(),
+            flags,
+            &mut storage as *mut _ as *mut _,
+            &mut len
+        )
+    })?;
+
+    if len == 0 {
+        return Err(IOError::new(ErrorKind::InvalidData, "datagram has no source address"));
+    }
+
+    Ok((bytes_read, sockaddr_to_socket_addr(&storage, len)?))
+}
+
+fn ip_mreq(multiaddr: Ipv4Addr, interface: Ipv4Addr) -> libc::ip_mreq {
+    libc::ip_mreq {
+        imr_multiaddr: libc::in_addr { s_addr: u32::from(multiaddr).to_be() },
+        imr_interface: libc::in_addr { s_addr: u32::from(interface).to_be() }
+    }
+}
+
+fn ipv6_mreq(multiaddr: Ipv6Addr, interface: u32) -> libc::ipv6_mreq {
+    libc::ipv6_mreq {
+        ipv6mr_multiaddr: libc::in6_addr { s6_addr: multiaddr.octets() },
+        ipv6mr_interface: interface
+    }
+}
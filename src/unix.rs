@@ -0,0 +1,610 @@
+use std::ffi::{c_int, OsStr};
+use std::future::Future;
+use std::io::{Error as IOError, ErrorKind, Result as IOResult};
+use std::net::Shutdown;
+use std::os::fd::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use crate::socket::{
+    add_write_fd,
+    check_os_error,
+    check_os_error_size,
+    new_socket,
+    read_fd_bytes,
+    remove_fd,
+    set_nonblocking,
+    shutdown_fd,
+    write_fd_bytes,
+    IoFuture,
+    RawAcceptFuture,
+    DEFAULT_BACKLOG
+};
+use crate::sockopt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum UnixSocketAddr {
+    Unnamed,
+    Pathname(PathBuf),
+    Abstract(Vec<u8>)
+}
+
+impl UnixSocketAddr {
+    pub fn from_pathname(path: impl AsRef<Path>) -> Self {
+        Self::Pathname(path.as_ref().to_path_buf())
+    }
+
+    pub fn from_abstract_name(name: impl AsRef<[u8]>) -> Self {
+        Self::Abstract(name.as_ref().to_vec())
+    }
+
+    pub fn as_pathname(&self) -> Option<&Path> {
+        match self {
+            Self::Pathname(path) => Some(path),
+            _ => None
+        }
+    }
+
+    pub fn as_abstract_name(&self) -> Option<&[u8]> {
+        match self {
+            Self::Abstract(name) => Some(name),
+            _ => None
+        }
+    }
+
+    pub fn is_unnamed(&self) -> bool {
+        matches!(self, Self::Unnamed)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct UCred {
+    pub pid: i32,
+    pub uid: u32,
+    pub gid: u32
+}
+
+#[derive(Debug)]
+pub struct ReceivedFds {
+    pub bytes_read: usize,
+    pub fds: Vec<OwnedFd>,
+    pub truncated: bool
+}
+
+pub(crate) fn sun_path_offset() -> usize {
+    std::mem::offset_of!(libc::sockaddr_un, sun_path)
+}
+
+pub(crate) fn unix_addr_to_sockaddr(addr: &UnixSocketAddr) -> IOResult<(libc::sockaddr_un, libc::socklen_t)> {
+    let mut sockaddr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    sockaddr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+
+    let (bytes, prefix) = match addr {
+        UnixSocketAddr::Unnamed => return Ok((sockaddr, sun_path_offset() as libc::socklen_t)),
+        UnixSocketAddr::Pathname(path) => (path.as_os_str().as_bytes(), 0),
+        UnixSocketAddr::Abstract(name) => (name.as_slice(), 1)
+    };
+
+    if prefix == 0 && bytes.contains(&0) {
+        return Err(IOError::new(ErrorKind::InvalidInput, "socket path must not contain NUL bytes"));
+    }
+
+    if bytes.len() >= sockaddr.sun_path.len() {
+        return Err(IOError::new(ErrorKind::InvalidInput, "socket path is too long"));
+    }
+
+    for (dst, src) in sockaddr.sun_path[prefix..].iter_mut().zip(bytes) {
+        *dst = *src as libc::c_char;
+    }
+
+    let len = match addr {
+        UnixSocketAddr::Pathname(_) => sun_path_offset() + bytes.len() + 1,
+        _ => sun_path_offset() + prefix + bytes.len()
+    };
+    Ok((sockaddr, len as libc::socklen_t))
+}
+
+pub(crate) fn sockaddr_to_unix_addr(sockaddr: &libc::sockaddr_un, len: libc::socklen_t) -> UnixSocketAddr {
+    let path_len = (len as usize).saturating_sub(sun_path_offset()).min(sockaddr.sun_path.len());
+    let path = unsafe { std::slice::from_raw_parts(sockaddr.sun_path.as_ptr() as *const u8, path_len) };
+
+    match path.first() {
+        None => UnixSocketAddr::Unnamed,
+        Some(0) => UnixSocketAddr::Abstract(path[1..].to_vec()),
+        Some(_) => {
+            let end = path.iter().position(|byte| *byte == 0).unwrap_or(path.len());
+            UnixSocketAddr::Pathname(PathBuf::from(OsStr::from_bytes(&path[..end])))
+        }
+    }
+}
+
+pub(crate) fn unix_getsockname(fd: c_int) -> IOResult<UnixSocketAddr> {
+    let mut sockaddr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&sockaddr) as libc::socklen_t;
+    check_os_error(unsafe { libc::getsockname(fd, &mut sockaddr as *mut _ as *mut _, &mut len) })?;
+    Ok(sockaddr_to_unix_addr(&sockaddr, len))
+}
+
+pub(crate) fn unix_getpeername(fd: c_int) -> IOResult<UnixSocketAddr> {
+    let mut sockaddr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of_val(&sockaddr) as libc::socklen_t;
+    check_os_error(unsafe { libc::getpeername(fd, &mut sockaddr as *mut _ as *mut _, &mut len) })?;
+    Ok(sockaddr_to_unix_addr(&sockaddr, len))
+}
+
+pub(crate) fn unix_socket(ty: c_int) -> IOResult<c_int> {
+    new_socket(libc::AF_UNIX, ty)
+}
+
+pub(crate) fn unix_socketpair(ty: c_int) -> IOResult<(c_int, c_int)> {
+    let mut fds = [0 as c_int; 2];
+    check_os_error(unsafe {
+        libc::socketpair(libc::AF_UNIX, ty | libc::SOCK_NONBLOCK | libc::SOCK_CLOEXEC, 0, fds.as_mut_ptr())
+    })?;
+    Ok((fds[0], fds[1]))
+}
+
+pub(crate) fn send_with_fds(fd: c_int, buf: &[u8], fds: &[RawFd]) -> IOResult<usize> {
+    let mut iov = libc::iovec { iov_base: buf.as_ptr() as *mut _, iov_len: buf.len() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let fds_len = std::mem::size_of_val(fds);
+    let mut cmsg_buf = vec![0u64; unsafe { libc::CMSG_SPACE(fds_len as u32) } as usize / 8 + 1];
+    if !fds.is_empty() {
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = unsafe { libc::CMSG_SPACE(fds_len as u32) } as _;
+        unsafe {
+            let cmsg = libc::CMSG_FIRSTHDR(&msg);
+            (*cmsg).cmsg_level = libc::SOL_SOCKET;
+            (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+            (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len as u32) as _;
+            std::ptr::copy_nonoverlapping(fds.as_ptr() as *const u8, libc::CMSG_DATA(cmsg), fds_len);
+        }
+    }
+
+    check_os_error_size(unsafe { libc::sendmsg(fd, &msg, libc::MSG_NOSIGNAL) })
+}
+
+pub(crate) fn recv_with_fds(fd: c_int, buf: &mut [u8], max_fds: usize) -> IOResult<ReceivedFds> {
+    let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut _, iov_len: buf.len() };
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+
+    let cmsg_space = unsafe { libc::CMSG_SPACE((max_fds * std::mem::size_of::<RawFd>()) as u32) } as usize;
+    let mut cmsg_buf = vec![0u64; cmsg_space / 8 + 1];
+    if max_fds != 0 {
+        msg.msg_control = cmsg_buf.as_mut_ptr() as *mut _;
+        msg.msg_controllen = cmsg_space as _;
+    }
+
+    let bytes_read = check_os_error_size(unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_CMSG_CLOEXEC) })?;
+
+    let mut fds = Vec::new();
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data_len = (*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize;
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                for i in 0..data_len / std::mem::size_of::<RawFd>() {
+                    fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+
+    let truncated = msg.msg_flags & libc::MSG_CTRUNC != 0;
+    Ok(ReceivedFds { bytes_read, fds, truncated })
+}
+
+pub(crate) fn peer_cred(fd: c_int) -> IOResult<UCred> {
+    let cred = sockopt::getsockopt::<libc::ucred>(fd, libc::SOL_SOCKET, libc::SO_PEERCRED)?;
+    Ok(UCred { pid: cred.pid, uid: cred.uid, gid: cred.gid })
+}
+
+#[derive(Debug)]
+pub struct UnixListener {
+    fd: c_int
+}
+
+impl UnixListener {
+    pub fn bind(path: impl AsRef<Path>) -> IOResult<Self> {
+        Self::bind_addr(&UnixSocketAddr::from_pathname(path))
+    }
+
+    pub fn bind_addr(addr: &UnixSocketAddr) -> IOResult<Self> {
+        Self::bind_addr_with_backlog(addr, DEFAULT_BACKLOG)
+    }
+
+    pub fn bind_addr_with_backlog(addr: &UnixSocketAddr, backlog: u32) -> IOResult<Self> {
+        let (sockaddr, len) = unix_addr_to_sockaddr(addr)?;
+        let listener = Self { fd: unix_socket(libc::SOCK_STREAM)? };
+
+        check_os_error(unsafe { libc::bind(listener.fd, &sockaddr as *const _ as *const _, len) })?;
+        check_os_error(unsafe { libc::listen(listener.fd, backlog.min(c_int::MAX as u32) as c_int) })?;
+        Ok(listener)
+    }
+
+    pub fn local_addr(&self) -> IOResult<UnixSocketAddr> {
+        unix_getsockname(self.fd)
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.fd)
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn accept(&mut self) -> Pin<Box<dyn Future<Output=IOResult<(UnixStream, UnixSocketAddr)>> + Send + Sync>> {
+        let accept = RawAcceptFuture::new(self.fd);
+        Box::pin(async move {
+            let (fd, storage, len) = accept.await?;
+            let sockaddr = unsafe { &*(&storage as *const _ as *const libc::sockaddr_un) };
+            Ok((UnixStream { fd }, sockaddr_to_unix_addr(sockaddr, len)))
+        })
+    }
+}
+
+impl AsRawFd for UnixListener {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl Drop for UnixListener {
+    fn drop(&mut self) {
+        remove_fd(self.fd);
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[derive(Debug)]
+pub struct UnixStream {
+    pub(crate) fd: c_int
+}
+
+impl UnixStream {
+    pub fn socketpair() -> IOResult<(UnixStream, UnixStream)> {
+        let (fd1, fd2) = unix_socketpair(libc::SOCK_STREAM)?;
+        Ok((UnixStream { fd: fd1 }, UnixStream { fd: fd2 }))
+    }
+
+    pub fn connect(path: impl AsRef<Path>) -> Pin<Box<dyn Future<Output=IOResult<UnixStream>> + Send + Sync>> {
+        Self::connect_addr(&UnixSocketAddr::from_pathname(path))
+    }
+
+    pub fn connect_addr(addr: &UnixSocketAddr) -> Pin<Box<dyn Future<Output=IOResult<UnixStream>> + Send + Sync>> {
+        struct ConnectFuture {
+            stream: Option<IOResult<UnixStream>>,
+            connecting: bool
+        }
+
+        impl Future for ConnectFuture {
+            type Output = IOResult<UnixStream>;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                let fd = match self.stream.as_ref().expect("ConnectFuture polled after completion") {
+                    Ok(stream) => stream.fd,
+                    Err(_) => return Poll::Ready(self.stream.take().unwrap())
+                };
+
+                if self.connecting {
+                    match sockopt::take_error(fd) {
+                        Ok(None) => {},
+                        Ok(Some(e)) | Err(e) => {
+                            self.stream = None;
+                            return Poll::Ready(Err(e));
+                        }
+                    }
+
+                    if let Err(e) = unix_getpeername(fd) {
+                        if e.raw_os_error() == Some(libc::ENOTCONN) {
+                            add_write_fd(fd, cx.waker().clone());
+                            return Poll::Pending;
+                        }
+
+                        self.stream = None;
+                        return Poll::Ready(Err(e));
+                    }
+                }
+
+                Poll::Ready(self.stream.take().unwrap())
+            }
+        }
+
+        let mut connecting = false;
+        let stream = unix_addr_to_sockaddr(addr).and_then(|(sockaddr, len)| {
+            let stream = UnixStream { fd: unix_socket(libc::SOCK_STREAM)? };
+            let result = unsafe { libc::connect(stream.fd, &sockaddr as *const _ as *const _, len) };
+            if result < 0 {
+                let e = IOError::last_os_error();
+                if e.raw_os_error() != Some(libc::EINPROGRESS) {
+                    return Err(e);
+                }
+                connecting = true;
+            }
+            Ok(stream)
+        });
+
+        Box::pin(ConnectFuture { stream: Some(stream), connecting })
+    }
+
+    pub fn local_addr(&self) -> IOResult<UnixSocketAddr> {
+        unix_getsockname(self.fd)
+    }
+
+    pub fn peer_addr(&self) -> IOResult<UnixSocketAddr> {
+        unix_getpeername(self.fd)
+    }
+
+    pub fn peer_cred(&self) -> IOResult<UCred> {
+        peer_cred(self.fd)
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.fd)
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> IOResult<()> {
+        shutdown_fd(self.fd, how)
+    }
+
+    pub fn read_bytes<'a>(
+        &self,
+        buf: &'a mut [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        read_fd_bytes(self.fd, buf)
+    }
+
+    pub fn write_bytes<'a>(
+        &mut self,
+        buf: &'a [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        write_fd_bytes(self.fd, buf)
+    }
+
+    pub fn send_with_fds<'a>(
+        &self,
+        buf: &'a [u8],
+        fds: &'a [RawFd]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::write(fd, move || send_with_fds(fd, buf, fds)))
+    }
+
+    pub fn recv_with_fds<'a>(
+        &self,
+        buf: &'a mut [u8],
+        max_fds: usize
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<ReceivedFds>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || recv_with_fds(fd, buf, max_fds)))
+    }
+}
+
+impl AsRawFd for UnixStream {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl FromRawFd for UnixStream {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let _ = set_nonblocking(fd);
+        Self { fd }
+    }
+}
+
+impl IntoRawFd for UnixStream {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        remove_fd(fd);
+        std::mem::forget(self);
+        fd
+    }
+}
+
+impl Drop for UnixStream {
+    fn drop(&mut self) {
+        remove_fd(self.fd);
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[derive(Debug)]
+pub struct UnixDatagram {
+    fd: c_int
+}
+
+impl UnixDatagram {
+    pub fn bind(path: impl AsRef<Path>) -> IOResult<Self> {
+        Self::bind_addr(&UnixSocketAddr::from_pathname(path))
+    }
+
+    pub fn bind_addr(addr: &UnixSocketAddr) -> IOResult<Self> {
+        let (sockaddr, len) = unix_addr_to_sockaddr(addr)?;
+        let socket = Self::unbound()?;
+        check_os_error(unsafe { libc::bind(socket.fd, &sockaddr as *const _ as *const _, len) })?;
+        Ok(socket)
+    }
+
+    pub fn unbound() -> IOResult<Self> {
+        Ok(Self { fd: unix_socket(libc::SOCK_DGRAM)? })
+    }
+
+    pub fn socketpair() -> IOResult<(UnixDatagram, UnixDatagram)> {
+        let (fd1, fd2) = unix_socketpair(libc::SOCK_DGRAM)?;
+        Ok((UnixDatagram { fd: fd1 }, UnixDatagram { fd: fd2 }))
+    }
+
+    pub fn connect(&self, path: impl AsRef<Path>) -> IOResult<()> {
+        self.connect_addr(&UnixSocketAddr::from_pathname(path))
+    }
+
+    pub fn connect_addr(&self, addr: &UnixSocketAddr) -> IOResult<()> {
+        let (sockaddr, len) = unix_addr_to_sockaddr(addr)?;
+        check_os_error(unsafe { libc::connect(self.fd, &sockaddr as *const _ as *const _, len) })?;
+        Ok(())
+    }
+
+    pub fn local_addr(&self) -> IOResult<UnixSocketAddr> {
+        unix_getsockname(self.fd)
+    }
+
+    pub fn peer_addr(&self) -> IOResult<UnixSocketAddr> {
+        unix_getpeername(self.fd)
+    }
+
+    pub fn peer_cred(&self) -> IOResult<UCred> {
+        peer_cred(self.fd)
+    }
+
+    pub fn take_error(&self) -> IOResult<Option<IOError>> {
+        sockopt::take_error(self.fd)
+    }
+
+    pub fn send_to<'a>(
+        &self,
+        buf: &'a [u8],
+        path: impl AsRef<Path>
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        self.send_to_addr(buf, &UnixSocketAddr::from_pathname(path))
+    }
+
+    pub fn send_to_addr<'a>(
+        &self,
+        buf: &'a [u8],
+        addr: &UnixSocketAddr
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        let (sockaddr, len) = match unix_addr_to_sockaddr(addr) {
+            Ok(sockaddr) => sockaddr,
+            Err(e) => return Box::pin(std::future::ready(Err(e)))
+        };
+
+        Box::pin(IoFuture::write(fd, move || check_os_error_size(unsafe {
+            libc::sendto(
+                fd,
+                buf.as_ptr() as *const _,
+                buf.len(),
+                libc::MSG_NOSIGNAL,
+                &sockaddr as *const _ as *const _,
+                len
+            )
+        })))
+    }
+
+    pub fn send<'a>(&self, buf: &'a [u8]) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::write(fd, move || check_os_error_size(unsafe {
+            libc::send(fd, buf.as_ptr() as *const _, buf.len(), libc::MSG_NOSIGNAL)
+        })))
+    }
+
+    #[allow(clippy::type_complexity)]
+    pub fn recv_from<'a>(
+        &self,
+        buf: &'a mut [u8]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<(usize, UnixSocketAddr)>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || {
+            let mut sockaddr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+            let mut len = std::mem::size_of_val(&sockaddr) as libc::socklen_t;
+            let bytes_read = check_os_error_size(unsafe {
+                libc::recvfrom(
+                    fd,
+                    buf.as_mut_ptr() as *mut _,
+                    buf.len(),
+                    0,
+                    &mut sockaddr as *mut _ as *mut _,
+                    &mut len
+                )
+            })?;
+            Ok((bytes_read, sockaddr_to_unix_addr(&sockaddr, len)))
+        }))
+    }
+
+    pub fn recv<'a>(&self, buf: &'a mut [u8]) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || check_os_error_size(unsafe {
+            libc::recv(fd, buf.as_mut_ptr() as *mut _, buf.len(), 0)
+        })))
+    }
+
+    pub fn send_with_fds<'a>(
+        &self,
+        buf: &'a [u8],
+        fds: &'a [RawFd]
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<usize>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::write(fd, move || send_with_fds(fd, buf, fds)))
+    }
+
+    pub fn recv_with_fds<'a>(
+        &self,
+        buf: &'a mut [u8],
+        max_fds: usize
+    ) -> Pin<Box<dyn 'a + Future<Output=IOResult<ReceivedFds>> + Send + Sync>> {
+        let fd = self.fd;
+        Box::pin(IoFuture::read(fd, move || recv_with_fds(fd, buf, max_fds)))
+    }
+}
+
+impl AsRawFd for UnixDatagram {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd
+    }
+}
+
+impl FromRawFd for UnixDatagram {
+    unsafe fn from_raw_fd(fd: RawFd) -> Self {
+        let _ = set_nonblocking(fd);
+        Self { fd }
+    }
+}
+
+impl IntoRawFd for UnixDatagram {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.fd;
+        remove_fd(fd);
+        std::mem::forget(self);
+        fd
+    }
+}
+
+impl Drop for UnixDatagram {
+    fn drop(&mut self) {
+        remove_fd(self.fd);
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::block_on;
+
+    #[test]
+    fn recv_with_fds_reports_truncated_fds() {
+        let received = block_on(async {
+            let (sender, receiver) = UnixDatagram::socketpair().unwrap();
+            let files = (0..3).map(|_| File::open("/dev/null").unwrap()).collect::<Vec<_>>();
+            let fds = files.iter().map(|file| file.as_raw_fd()).collect::<Vec<_>>();
+            sender.send_with_fds(b"fds", &fds).await.unwrap();
+
+            let mut buf = [0u8; 8];
+            let received = receiver.recv_with_fds(&mut buf, 1).await.unwrap();
+            assert_eq!(&buf[..received.bytes_read], b"fds");
+            received
+        });
+        assert!(!received.fds.is_empty() && received.fds.len() < 3);
+        assert!(received.truncated);
+    }
+}